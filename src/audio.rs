@@ -1,14 +1,20 @@
 use bevy::prelude::*;
 
-use crate::game_mechanics::{HitTrapEvent, PlayerMovedEvent, ReachedGoalEvent, TimerExpiredEvent};
+use crate::game_mechanics::{
+    GoalCollectedEvent, HitTrapEvent, KeyPickedUpEvent, PlayerMovedEvent, ReachedGoalEvent,
+    TeleportedEvent, TimerExpiredEvent,
+};
 
 pub struct AudioPlugin;
 
 impl Plugin for AudioPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Update, play_on_goal_reached)
+            .add_systems(Update, play_on_goal_collected)
+            .add_systems(Update, play_on_key_picked_up)
             .add_systems(Update, play_on_hit_trap)
             .add_systems(Update, play_on_change_pos)
+            .add_systems(Update, play_on_teleported)
             .add_systems(Update, play_on_timer_elapse);
     }
 }
@@ -28,6 +34,38 @@ fn play_on_goal_reached(
     }
 }
 
+/// The level-reached sound played faster, so collecting a goal sounds lighter than finishing
+fn play_on_goal_collected(
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+    mut event: EventReader<GoalCollectedEvent>,
+) {
+    for _ in event.iter() {
+        commands.spawn(
+            AudioBundle {
+                source: asset_server.load("sounds/level-reached.ogg"),
+                settings: PlaybackSettings::ONCE.with_speed(1.5),
+            }
+        );
+    }
+}
+
+/// The move sound played twice as fast, a short high blip
+fn play_on_key_picked_up(
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+    mut event: EventReader<KeyPickedUpEvent>,
+) {
+    for _ in event.iter() {
+        commands.spawn(
+            AudioBundle {
+                source: asset_server.load("sounds/move.ogg"),
+                settings: PlaybackSettings::ONCE.with_speed(2.0),
+            }
+        );
+    }
+}
+
 fn play_on_hit_trap(
     asset_server: Res<AssetServer>,
     mut commands: Commands,
@@ -72,3 +110,19 @@ fn play_on_change_pos(
         );
     }
 }
+
+/// The move sound played at half speed, a low whoosh
+fn play_on_teleported(
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+    mut event: EventReader<TeleportedEvent>,
+) {
+    for _ in event.iter() {
+        commands.spawn(
+            AudioBundle {
+                source: asset_server.load("sounds/move.ogg"),
+                settings: PlaybackSettings::ONCE.with_speed(0.5),
+            }
+        );
+    }
+}
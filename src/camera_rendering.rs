@@ -15,13 +15,16 @@ use bevy::{
 };
 use ctrl_macros::ok_or_return;
 
-use crate::game_mechanics::{GRID_SIZE_X, GRID_SIZE_Y};
+use crate::game_mechanics::LevelSize;
 
 pub struct CameraRendering;
 
 #[derive(Component)]
 struct CameraStuff;
 
+#[derive(Component)]
+struct FloorTile;
+
 impl Plugin for CameraRendering {
     fn build(&self, app: &mut App) {
         app.add_plugins(Material2dPlugin::<PostProcessingMaterialRed>::default())
@@ -30,9 +33,10 @@ impl Plugin for CameraRendering {
             .add_systems(Startup, setup_main_camera)
             .add_systems(Startup, setup)
             .add_systems(Startup, setup_cameras)
-            .add_systems(Startup, spawn_first_level)
             // .add_systems(Update, set_camera_viewports)
-            .add_systems(Update, recreate_on_resize);
+            .add_systems(Update, recreate_on_resize)
+            .add_systems(Update, spawn_floor_on_level_size_change)
+            .add_systems(Update, frame_cameras_on_level_size_change);
         // .add_systems(Update, resize_camera_sprites);
     }
 }
@@ -53,6 +57,7 @@ fn setup_cameras(
     mut post_processing_materials_blue: ResMut<Assets<PostProcessingMaterialBlue>>,
     mut meshes: ResMut<Assets<Mesh>>,
     q_window: Query<(Entity, &Window), With<PrimaryWindow>>,
+    level_size: Res<LevelSize>,
 ) {
     let window = ok_or_return!(q_window.get_single()).1;
     let size = Extent3d {
@@ -164,8 +169,7 @@ fn setup_cameras(
                 }),
                 ..default()
             },
-            transform: Transform::from_xyz(3.0, 35.0, 12.0)
-                .looking_at(Vec3::new(3.0, 10.0, 5.0), Vec3::Y),
+            transform: camera_transform(&level_size),
 
             ..default()
         });
@@ -217,11 +221,21 @@ fn setup(mut commands: Commands) {
     });
 }
 
-fn spawn_first_level(
+fn spawn_floor_on_level_size_change(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    level_size: Res<LevelSize>,
+    q_floor_tiles: Query<Entity, With<FloorTile>>,
 ) {
+    if !level_size.is_changed() {
+        return;
+    }
+
+    for entity in q_floor_tiles.iter() {
+        commands.entity(entity).despawn();
+    }
+
     let material_handle = materials.add(StandardMaterial {
         base_color: Color::rgb(0.8, 0.8, 0.8),
         ..default()
@@ -230,8 +244,8 @@ fn spawn_first_level(
     let element_size = 0.95;
     let padding = 0.05;
 
-    for i in 0..GRID_SIZE_X {
-        for j in 0..GRID_SIZE_Y {
+    for i in 0..level_size.width {
+        for j in 0..level_size.height {
             let x = i as f32;
             let y = j as f32;
             let x = x * (element_size + padding);
@@ -244,11 +258,42 @@ fn spawn_first_level(
                     ..default()
                 })
                 .insert(RenderLayers::from_layers(&[1, 2, 3]))
+                .insert(FloorTile)
                 .insert(Name::new("Plane"));
         }
     }
 }
 
+fn frame_cameras_on_level_size_change(
+    level_size: Res<LevelSize>,
+    mut q_cameras: Query<&mut Transform, (With<Camera3d>, With<CameraStuff>)>,
+) {
+    if !level_size.is_changed() {
+        return;
+    }
+
+    for mut transform in q_cameras.iter_mut() {
+        *transform = camera_transform(&level_size);
+    }
+}
+
+/// Camera looking at the middle of the level, pulled back so the whole level fits in the view.
+/// The framing was tuned for an 8x12 level and is scaled from there.
+fn camera_transform(level_size: &LevelSize) -> Transform {
+    let center = Vec3::new(
+        (level_size.width - 1) as f32 / 2.0,
+        0.0,
+        (level_size.height - 1) as f32 / 2.0,
+    );
+    // Don't zoom in too much on tiny levels (or before any level is loaded)
+    let scale = (level_size.width as f32 / 8.0)
+        .max(level_size.height as f32 / 12.0)
+        .max(0.5);
+
+    Transform::from_translation(center + scale * Vec3::new(0.0, 35.0, 6.5))
+        .looking_at(center + scale * Vec3::new(0.0, 10.0, -0.5), Vec3::Y)
+}
+
 #[derive(AsBindGroup, TypeUuid, Clone, TypePath)]
 #[uuid = "bc1812d4-ba8e-4cc8-87c1-84ef07a7cb7b"]
 struct PostProcessingMaterialRed {
@@ -375,6 +420,7 @@ fn recreate_on_resize(
     q_window: Query<(Entity, &Window), With<PrimaryWindow>>,
     mut resize_events: EventReader<WindowResized>,
     q_camera_stuff: Query<Entity, With<CameraStuff>>,
+    level_size: Res<LevelSize>,
 ) {
     let window_entity = ok_or_return!(q_window.get_single()).0;
     let mut has_resize = false;
@@ -406,5 +452,6 @@ fn recreate_on_resize(
         post_processing_materials_blue,
         meshes,
         q_window,
+        level_size,
     );
 }
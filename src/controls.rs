@@ -0,0 +1,710 @@
+use std::{collections::HashMap, time::Duration};
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    ecs::system::SystemParam,
+    input::InputSystem,
+    prelude::*,
+    reflect::TypePath,
+    utils::BoxedFuture,
+};
+use ctrl_macros::some_or_return;
+use serde::{Deserialize, Serialize};
+
+#[cfg(target_arch = "wasm32")]
+use crate::progress::local_storage;
+
+/// How far a stick has to be pushed before it counts as a move
+const STICK_DEAD_ZONE: f32 = 0.5;
+/// How long a move has to be held on a gamepad before it starts repeating
+const REPEAT_DELAY: Duration = Duration::from_millis(400);
+/// Time between repeated moves while a move stays held
+const REPEAT_INTERVAL: Duration = Duration::from_millis(150);
+/// Starts and stops rebinding in game. It can't be rebound itself, so it's never lost.
+pub const REBIND_KEY: KeyCode = KeyCode::F1;
+
+/// Where bindings made in game are kept between runs
+#[cfg(not(any(test, target_arch = "wasm32")))]
+const REBOUND_PATH: &str = "rebound-controls.json";
+
+/// Where the web build keeps bindings made in game between runs, in the browser's local storage
+#[cfg(target_arch = "wasm32")]
+const REBOUND_KEY: &str = "rgb-rebound-controls";
+
+pub struct ControlsPlugin;
+
+/// What the player wants to do, whatever they pressed to ask for it
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum InputAction {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    SwitchPlayer,
+    Undo,
+    Restart,
+    Pause,
+}
+
+impl InputAction {
+    pub const ALL: [InputAction; 8] = [
+        InputAction::MoveUp,
+        InputAction::MoveDown,
+        InputAction::MoveLeft,
+        InputAction::MoveRight,
+        InputAction::SwitchPlayer,
+        InputAction::Undo,
+        InputAction::Restart,
+        InputAction::Pause,
+    ];
+
+    /// Whether holding the action keeps doing it
+    fn repeats(self) -> bool {
+        matches!(
+            self,
+            InputAction::MoveUp
+                | InputAction::MoveDown
+                | InputAction::MoveLeft
+                | InputAction::MoveRight
+        )
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            InputAction::MoveUp => "up",
+            InputAction::MoveDown => "down",
+            InputAction::MoveLeft => "left",
+            InputAction::MoveRight => "right",
+            InputAction::SwitchPlayer => "switch player",
+            InputAction::Undo => "undo",
+            InputAction::Restart => "restart",
+            InputAction::Pause => "pause",
+        }
+    }
+}
+
+/// Keys and gamepad buttons bound to each action. Loaded from `controls.bindings.json`, which is
+/// picked up again when edited, with the actions rebound in game on top. The left stick always
+/// moves.
+#[derive(Serialize, Deserialize, bevy::reflect::TypeUuid, Resource, TypePath, Clone, Debug)]
+#[uuid = "8c1b7f0e-52d4-4b8a-9a37-0f4d3c6e21a5"] // <-- keep me unique
+pub struct Bindings {
+    #[serde(default)]
+    pub keys: HashMap<InputAction, Vec<KeyCode>>,
+    #[serde(default)]
+    pub buttons: HashMap<InputAction, Vec<GamepadButtonType>>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings {
+            keys: HashMap::from([
+                (InputAction::MoveUp, vec![KeyCode::W, KeyCode::Up]),
+                (InputAction::MoveDown, vec![KeyCode::S, KeyCode::Down]),
+                (InputAction::MoveLeft, vec![KeyCode::A, KeyCode::Left]),
+                (InputAction::MoveRight, vec![KeyCode::D, KeyCode::Right]),
+                (InputAction::SwitchPlayer, vec![KeyCode::Tab]),
+                (InputAction::Undo, vec![KeyCode::Z]),
+                (InputAction::Restart, vec![KeyCode::R]),
+                (InputAction::Pause, vec![KeyCode::Escape, KeyCode::P]),
+            ]),
+            buttons: HashMap::from([
+                (InputAction::MoveUp, vec![GamepadButtonType::DPadUp]),
+                (InputAction::MoveDown, vec![GamepadButtonType::DPadDown]),
+                (InputAction::MoveLeft, vec![GamepadButtonType::DPadLeft]),
+                (InputAction::MoveRight, vec![GamepadButtonType::DPadRight]),
+                (InputAction::SwitchPlayer, vec![GamepadButtonType::South]),
+                (InputAction::Undo, vec![GamepadButtonType::West]),
+                (InputAction::Restart, vec![GamepadButtonType::North]),
+                (InputAction::Pause, vec![GamepadButtonType::Start]),
+            ]),
+        }
+    }
+}
+
+impl Bindings {
+    /// No action bound to anything
+    fn none() -> Bindings {
+        Bindings {
+            keys: HashMap::new(),
+            buttons: HashMap::new(),
+        }
+    }
+
+    /// Binds the actions listed in `other` to its keys and buttons, keeping the rest as they are
+    pub fn merge(&mut self, other: &Bindings) {
+        for (action, keys) in &other.keys {
+            self.keys.insert(*action, keys.clone());
+        }
+        for (action, buttons) in &other.buttons {
+            self.buttons.insert(*action, buttons.clone());
+        }
+    }
+
+    pub fn keys(&self, action: InputAction) -> &[KeyCode] {
+        self.keys.get(&action).map_or(&[], |keys| keys.as_slice())
+    }
+
+    pub fn buttons(&self, action: InputAction) -> &[GamepadButtonType] {
+        self.buttons
+            .get(&action)
+            .map_or(&[], |buttons| buttons.as_slice())
+    }
+
+    /// One line listing every action and its keys, for showing to the player
+    pub fn describe(&self) -> String {
+        let actions: Vec<String> = InputAction::ALL
+            .iter()
+            .filter(|action| !self.keys(**action).is_empty())
+            .map(|action| {
+                let keys: Vec<String> = self
+                    .keys(*action)
+                    .iter()
+                    .map(|key| format!("{key:?}"))
+                    .collect();
+                format!("{} {}", keys.join("/"), action.label())
+            })
+            .collect();
+        actions.join(", ")
+    }
+}
+
+/// Everything connected gamepads are doing
+#[derive(SystemParam)]
+pub struct GamepadInputs<'w> {
+    gamepads: Res<'w, Gamepads>,
+    buttons: Res<'w, Input<GamepadButton>>,
+    axes: Res<'w, Axis<GamepadAxis>>,
+}
+
+impl GamepadInputs<'_> {
+    fn pressed(&self, button: GamepadButtonType) -> bool {
+        self.gamepads
+            .iter()
+            .any(|gamepad| self.buttons.pressed(GamepadButton::new(gamepad, button)))
+    }
+
+    /// Moves the left stick of any gamepad is pushed toward
+    fn stick_actions(&self) -> Vec<InputAction> {
+        self.gamepads
+            .iter()
+            .filter_map(|gamepad| {
+                let x = self
+                    .axes
+                    .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX))?;
+                let y = self
+                    .axes
+                    .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY))?;
+                stick_action(x, y)
+            })
+            .collect()
+    }
+}
+
+/// The action waiting for a key or button to be bound to it, while rebinding in game
+#[derive(Resource, Default)]
+pub struct Rebinding(pub Option<InputAction>);
+
+/// Just the actions rebound in game, saved so they outlast restarts and edits of the bindings file
+#[derive(Resource)]
+struct Rebound(Bindings);
+
+#[derive(Resource)]
+struct BindingsHandle(Handle<Bindings>);
+
+#[derive(Default)]
+struct BindingsLoader;
+
+impl AssetLoader for BindingsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let bindings: Bindings = serde_json::from_slice(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(bindings));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["bindings.json"]
+    }
+}
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        let rebound = load_rebound();
+        let mut bindings = Bindings::default();
+        bindings.merge(&rebound.0);
+
+        app.add_asset::<Bindings>()
+            .add_asset_loader(BindingsLoader)
+            .insert_resource(bindings)
+            .insert_resource(rebound)
+            .init_resource::<Input<InputAction>>()
+            .init_resource::<Rebinding>()
+            .add_systems(Startup, setup)
+            .add_systems(Update, apply_loaded_bindings)
+            .add_systems(
+                PreUpdate,
+                (rebind_actions, update_actions).chain().after(InputSystem),
+            );
+    }
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let handle: Handle<Bindings> = asset_server.load("controls.bindings.json");
+    commands.insert_resource(BindingsHandle(handle));
+}
+
+fn apply_loaded_bindings(
+    mut ev_asset: EventReader<AssetEvent<Bindings>>,
+    handle: Res<BindingsHandle>,
+    assets: Res<Assets<Bindings>>,
+    rebound: Res<Rebound>,
+    mut bindings: ResMut<Bindings>,
+) {
+    for ev in ev_asset.iter() {
+        match ev {
+            AssetEvent::Created { handle: changed } | AssetEvent::Modified { handle: changed }
+                if *changed == handle.0 =>
+            {
+                if let Some(loaded) = assets.get(&handle.0) {
+                    let mut updated = Bindings::default();
+                    updated.merge(loaded);
+                    updated.merge(&rebound.0);
+                    *bindings = updated;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Binds the next key or button pressed to the action waiting for one, going through every
+/// action in turn. [`REBIND_KEY`] starts over, or stops early keeping the rest as they were.
+fn rebind_actions(
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut bindings: ResMut<Bindings>,
+    mut rebound: ResMut<Rebound>,
+    mut actions: ResMut<Input<InputAction>>,
+) {
+    if keys.just_pressed(REBIND_KEY) {
+        rebinding.0 = match rebinding.0 {
+            Some(_) => None,
+            None => Some(InputAction::ALL[0]),
+        };
+        return;
+    }
+
+    let action = some_or_return!(rebinding.0);
+    let mut rebind = Bindings::none();
+    if let Some(&key) = keys.get_just_pressed().next() {
+        rebind.keys.insert(action, vec![key]);
+    } else if let Some(button) = buttons.get_just_pressed().next() {
+        rebind.buttons.insert(action, vec![button.button_type]);
+    } else {
+        return;
+    }
+    bindings.merge(&rebind);
+    rebound.0.merge(&rebind);
+    save_rebound(&rebound);
+
+    // Already held, so it doesn't go off as soon as rebinding is over
+    actions.press(action);
+    rebinding.0 = InputAction::ALL
+        .into_iter()
+        .skip_while(|&other| other != action)
+        .nth(1);
+}
+
+#[cfg(not(any(test, target_arch = "wasm32")))]
+fn load_rebound() -> Rebound {
+    let bytes = match std::fs::read(REBOUND_PATH) {
+        Ok(bytes) => bytes,
+        Err(_) => return Rebound(Bindings::none()),
+    };
+
+    Rebound(serde_json::from_slice(&bytes).unwrap_or_else(|err| {
+        warn!("Ignoring unreadable {REBOUND_PATH}: {err}");
+        Bindings::none()
+    }))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn load_rebound() -> Rebound {
+    let json = match local_storage().and_then(|storage| storage.get_item(REBOUND_KEY).ok()?) {
+        Some(json) => json,
+        None => return Rebound(Bindings::none()),
+    };
+
+    Rebound(serde_json::from_str(&json).unwrap_or_else(|err| {
+        warn!("Ignoring unreadable {REBOUND_KEY}: {err}");
+        Bindings::none()
+    }))
+}
+
+/// Tests leave the player's own bindings alone
+#[cfg(test)]
+fn load_rebound() -> Rebound {
+    Rebound(Bindings::none())
+}
+
+#[cfg(not(any(test, target_arch = "wasm32")))]
+fn save_rebound(rebound: &Rebound) {
+    let result = serde_json::to_vec_pretty(&rebound.0)
+        .map_err(anyhow::Error::from)
+        .and_then(|bytes| Ok(std::fs::write(REBOUND_PATH, bytes)?));
+
+    if let Err(err) = result {
+        warn!("Couldn't save bindings to {REBOUND_PATH}: {err}");
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn save_rebound(rebound: &Rebound) {
+    let result = serde_json::to_string(&rebound.0)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| {
+            let storage = local_storage().ok_or_else(|| anyhow::anyhow!("no local storage"))?;
+            storage
+                .set_item(REBOUND_KEY, &json)
+                .map_err(|err| anyhow::anyhow!("{err:?}"))
+        });
+
+    if let Err(err) = result {
+        warn!("Couldn't save bindings to {REBOUND_KEY}: {err}");
+    }
+}
+
+#[cfg(test)]
+fn save_rebound(_rebound: &Rebound) {}
+
+/// The move a stick pushed this far points to, along whichever axis it's pushed further
+fn stick_action(x: f32, y: f32) -> Option<InputAction> {
+    if x.abs().max(y.abs()) < STICK_DEAD_ZONE {
+        None
+    } else if x.abs() > y.abs() {
+        Some(if x > 0.0 {
+            InputAction::MoveRight
+        } else {
+            InputAction::MoveLeft
+        })
+    } else {
+        Some(if y > 0.0 {
+            InputAction::MoveUp
+        } else {
+            InputAction::MoveDown
+        })
+    }
+}
+
+/// Presses and releases actions to follow the keys and buttons bound to them, on any gamepad.
+/// Moves held on a gamepad are pressed again every so often, while held keys only move once.
+/// Nothing is pressed while rebinding.
+pub fn update_actions(
+    keys: Res<Input<KeyCode>>,
+    gamepad: GamepadInputs,
+    bindings: Res<Bindings>,
+    rebinding: Res<Rebinding>,
+    time: Res<Time>,
+    mut held_for: Local<HashMap<InputAction, Duration>>,
+    mut actions: ResMut<Input<InputAction>>,
+) {
+    actions.clear();
+    if rebinding.0.is_some() {
+        actions.release_all();
+        return;
+    }
+
+    let sticks = gamepad.stick_actions();
+
+    for action in InputAction::ALL {
+        let on_keys = bindings.keys(action).iter().any(|key| keys.pressed(*key));
+        let on_gamepad = bindings
+            .buttons(action)
+            .iter()
+            .any(|button| gamepad.pressed(*button))
+            || sticks.contains(&action);
+        let pressed = on_keys || on_gamepad;
+
+        if pressed && !actions.pressed(action) {
+            actions.press(action);
+            held_for.insert(action, Duration::ZERO);
+        } else if on_gamepad && action.repeats() {
+            let held = held_for.entry(action).or_default();
+            *held += time.delta();
+            if *held >= REPEAT_DELAY {
+                *held -= REPEAT_INTERVAL;
+                actions.release(action);
+                actions.press(action);
+            }
+        } else if !pressed && actions.pressed(action) {
+            actions.release(action);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        asset::LoadState,
+        input::{
+            gamepad::{
+                GamepadAxisChangedEvent, GamepadButtonChangedEvent, GamepadConnection,
+                GamepadConnectionEvent, GamepadEvent, GamepadInfo,
+            },
+            keyboard::KeyboardInput,
+            ButtonState, InputPlugin,
+        },
+        time::TimeUpdateStrategy,
+    };
+
+    use super::*;
+
+    /// Runs the controls without a window, a frame taking 100ms
+    fn headless_app() -> App {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            InputPlugin,
+            ControlsPlugin,
+        ))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+            100,
+        )));
+
+        // Let the bindings file load first, so it can't replace bindings made during a test
+        app.update();
+        let handle = app.world.resource::<BindingsHandle>().0.clone();
+        for _ in 0..1000 {
+            if app.world.resource::<AssetServer>().get_load_state(&handle) == LoadState::Loaded {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        // One frame to store the loaded asset, one more to apply it
+        app.update();
+        app.update();
+        app
+    }
+
+    fn tap_key(app: &mut App, key: KeyCode) {
+        for state in [ButtonState::Pressed, ButtonState::Released] {
+            app.world.send_event(KeyboardInput {
+                scan_code: 0,
+                key_code: Some(key),
+                state,
+                window: Entity::PLACEHOLDER,
+            });
+            app.update();
+        }
+    }
+
+    fn send(app: &mut App, event: impl Into<GamepadEvent>) {
+        app.world.send_event(event.into());
+        app.update();
+    }
+
+    fn connect(app: &mut App, id: usize) {
+        let info = GamepadInfo {
+            name: format!("Pad {id}"),
+        };
+        send(
+            app,
+            GamepadConnectionEvent::new(Gamepad::new(id), GamepadConnection::Connected(info)),
+        );
+    }
+
+    fn actions(app: &App) -> &Input<InputAction> {
+        app.world.resource::<Input<InputAction>>()
+    }
+
+    #[test]
+    fn buttons_on_any_pad_trigger_actions() {
+        let mut app = headless_app();
+        connect(&mut app, 0);
+        connect(&mut app, 1);
+
+        let pad = Gamepad::new(1);
+        send(
+            &mut app,
+            GamepadButtonChangedEvent::new(pad, GamepadButtonType::DPadUp, 1.0),
+        );
+        assert!(actions(&app).just_pressed(InputAction::MoveUp));
+
+        send(
+            &mut app,
+            GamepadButtonChangedEvent::new(pad, GamepadButtonType::DPadUp, 0.0),
+        );
+        send(
+            &mut app,
+            GamepadButtonChangedEvent::new(pad, GamepadButtonType::North, 1.0),
+        );
+        assert!(!actions(&app).pressed(InputAction::MoveUp));
+        assert!(actions(&app).just_pressed(InputAction::Restart));
+    }
+
+    #[test]
+    fn stick_moves_outside_dead_zone() {
+        let mut app = headless_app();
+        connect(&mut app, 0);
+
+        let pad = Gamepad::new(0);
+        send(
+            &mut app,
+            GamepadAxisChangedEvent::new(pad, GamepadAxisType::LeftStickX, 0.3),
+        );
+        send(
+            &mut app,
+            GamepadAxisChangedEvent::new(pad, GamepadAxisType::LeftStickY, -0.2),
+        );
+        assert!(actions(&app).get_pressed().next().is_none());
+
+        send(
+            &mut app,
+            GamepadAxisChangedEvent::new(pad, GamepadAxisType::LeftStickY, -0.9),
+        );
+        assert!(actions(&app).just_pressed(InputAction::MoveDown));
+    }
+
+    #[test]
+    fn held_moves_repeat_after_a_delay() {
+        let mut app = headless_app();
+        connect(&mut app, 0);
+
+        send(
+            &mut app,
+            GamepadButtonChangedEvent::new(Gamepad::new(0), GamepadButtonType::DPadRight, 1.0),
+        );
+        assert!(actions(&app).just_pressed(InputAction::MoveRight));
+
+        let mut repeats = vec![];
+        for _ in 0..10 {
+            app.update();
+            repeats.push(actions(&app).just_pressed(InputAction::MoveRight));
+        }
+        assert!(!repeats[..3].contains(&true), "repeated too soon");
+        assert!(repeats.iter().filter(|repeat| **repeat).count() >= 3);
+    }
+
+    #[test]
+    fn held_keys_dont_repeat() {
+        let mut app = headless_app();
+
+        app.world.send_event(KeyboardInput {
+            scan_code: 0,
+            key_code: Some(KeyCode::D),
+            state: ButtonState::Pressed,
+            window: Entity::PLACEHOLDER,
+        });
+        app.update();
+        assert!(actions(&app).just_pressed(InputAction::MoveRight));
+
+        for _ in 0..10 {
+            app.update();
+            assert!(actions(&app).pressed(InputAction::MoveRight));
+            assert!(!actions(&app).just_pressed(InputAction::MoveRight));
+        }
+    }
+
+    #[test]
+    fn shipped_bindings_cover_every_action() {
+        let bytes = std::fs::read("assets/controls.bindings.json").unwrap();
+        let bindings: Bindings = serde_json::from_slice(&bytes).unwrap();
+
+        for action in InputAction::ALL {
+            assert!(!bindings.keys(action).is_empty(), "{action:?} has no key");
+            assert!(
+                !bindings.buttons(action).is_empty(),
+                "{action:?} has no button"
+            );
+        }
+    }
+
+    #[test]
+    fn loaded_bindings_keep_defaults_for_missing_actions() {
+        let loaded: Bindings =
+            serde_json::from_str(r#"{ "keys": { "Undo": ["U", "Back"] } }"#).unwrap();
+        let mut bindings = Bindings::default();
+        bindings.merge(&loaded);
+
+        assert_eq!(
+            bindings.keys(InputAction::Undo),
+            [KeyCode::U, KeyCode::Back]
+        );
+        assert_eq!(bindings.keys(InputAction::Restart), [KeyCode::R]);
+        assert_eq!(
+            bindings.buttons(InputAction::Undo),
+            [GamepadButtonType::West]
+        );
+    }
+
+    #[test]
+    fn actions_are_rebound_in_game_one_after_another() {
+        let mut app = headless_app();
+
+        tap_key(&mut app, REBIND_KEY);
+        assert_eq!(
+            app.world.resource::<Rebinding>().0,
+            Some(InputAction::MoveUp)
+        );
+        tap_key(&mut app, KeyCode::I);
+        tap_key(&mut app, KeyCode::K);
+        assert_eq!(
+            app.world.resource::<Rebinding>().0,
+            Some(InputAction::MoveLeft)
+        );
+        tap_key(&mut app, REBIND_KEY);
+        assert_eq!(app.world.resource::<Rebinding>().0, None);
+
+        let bindings = app.world.resource::<Bindings>();
+        assert_eq!(bindings.keys(InputAction::MoveUp), [KeyCode::I]);
+        assert_eq!(bindings.keys(InputAction::MoveDown), [KeyCode::K]);
+        assert_eq!(
+            bindings.keys(InputAction::MoveLeft),
+            [KeyCode::A, KeyCode::Left]
+        );
+
+        app.world.send_event(KeyboardInput {
+            scan_code: 0,
+            key_code: Some(KeyCode::I),
+            state: ButtonState::Pressed,
+            window: Entity::PLACEHOLDER,
+        });
+        app.update();
+        assert!(actions(&app).just_pressed(InputAction::MoveUp));
+    }
+
+    #[test]
+    fn actions_rebound_in_game_outlast_reloading_the_bindings_file() {
+        let mut app = headless_app();
+        tap_key(&mut app, REBIND_KEY);
+        tap_key(&mut app, KeyCode::I);
+        tap_key(&mut app, REBIND_KEY);
+
+        // Only what was rebound is saved
+        let rebound = &app.world.resource::<Rebound>().0;
+        assert_eq!(
+            rebound.keys,
+            HashMap::from([(InputAction::MoveUp, vec![KeyCode::I])])
+        );
+        assert!(rebound.buttons.is_empty());
+
+        let handle = app.world.resource::<BindingsHandle>().0.clone();
+        app.world.send_event(AssetEvent::Modified { handle });
+        app.update();
+
+        let bindings = app.world.resource::<Bindings>();
+        assert_eq!(bindings.keys(InputAction::MoveUp), [KeyCode::I]);
+        assert_eq!(
+            bindings.keys(InputAction::MoveDown),
+            [KeyCode::S, KeyCode::Down]
+        );
+    }
+}
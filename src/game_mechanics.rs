@@ -1,10 +1,12 @@
-use bevy::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*};
 
-use ctrl_macros::{ok_or_return, some_or_return};
+use ctrl_macros::{some_or_continue, some_or_return};
 use serde::Deserialize;
 
-pub const GRID_SIZE_X: i32 = 8;
-pub const GRID_SIZE_Y: i32 = 12;
+use crate::{
+    controls::InputAction,
+    game_state::{Action, GameState, Outcome},
+};
 
 pub struct GameMechanicsPlugin;
 
@@ -16,13 +18,85 @@ pub struct Goal;
 #[derive(Component)]
 pub struct Trap;
 
-#[derive(Component, Copy, Clone, PartialEq, Eq)]
+/// Whether a goal has been collected, or a key picked up
+#[derive(Component, PartialEq)]
+pub struct Collected(pub bool);
+
+/// Index of an object in its list in [`GameState`]
+#[derive(Component, Copy, Clone)]
+pub struct ObjectIndex(pub usize);
+
+#[derive(Component)]
+pub struct Wall;
+
+#[derive(Component)]
+pub struct PaintTile;
+
+#[derive(Component)]
+pub struct Teleporter;
+
+#[derive(Component)]
+pub struct Ice;
+
+#[derive(Component)]
+pub struct Block;
+
+#[derive(Component)]
+pub struct Key;
+
+#[derive(Component)]
+pub struct Door;
+
+#[derive(Component)]
+pub struct Switch;
+
+/// Whether a trap is switched on
+#[derive(Component, PartialEq)]
+pub struct Active(pub bool);
+
+/// Whether a player is the one in control, in levels where only one moves at a time
+#[derive(Component, PartialEq)]
+pub struct Controlled(pub bool);
+
+/// Colors of the keys the player holds
+#[derive(Component, Default, PartialEq)]
+pub struct Inventory(pub Vec<GameColor>);
+
+/// Whether a trap has been filled by a block, or a block has sunk into a trap to fill it
+#[derive(Component, PartialEq)]
+pub struct Filled(pub bool);
+
+#[derive(Component, Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct GridPos {
     pub x: i32,
     pub y: i32,
 }
 
-#[derive(Component, Copy, Clone, Deserialize)]
+impl GridPos {
+    pub fn step(self, direction: Direction) -> GridPos {
+        let (dx, dy) = match direction {
+            Direction::Up => (0, 1),
+            Direction::Down => (0, -1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+        };
+
+        GridPos {
+            x: self.x + dx,
+            y: self.y + dy,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[derive(Component, Copy, Clone, PartialEq, Eq, Hash, Debug, Deserialize)]
 pub enum GameColor {
     Red,
     Green,
@@ -33,111 +107,342 @@ pub enum GameColor {
     White,
 }
 
+impl GameColor {
+    /// The red, green and blue channels the color is made of, one bit each
+    pub fn channels(self) -> u8 {
+        match self {
+            GameColor::Red => 0b100,
+            GameColor::Green => 0b010,
+            GameColor::Blue => 0b001,
+            GameColor::Yellow => 0b110,
+            GameColor::Cyan => 0b011,
+            GameColor::Pink => 0b101,
+            GameColor::White => 0b111,
+        }
+    }
+
+    pub fn shares_channel_with(self, other: GameColor) -> bool {
+        self.channels() & other.channels() != 0
+    }
+}
+
+/// Size of the grid of the currently loaded level
+#[derive(Resource, Default, Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct LevelSize {
+    pub width: i32,
+    pub height: i32,
+}
+
+impl LevelSize {
+    pub fn contains(&self, pos: GridPos) -> bool {
+        pos.x >= 0 && pos.x < self.width && pos.y >= 0 && pos.y < self.height
+    }
+}
+
 // Events
 
 #[derive(Event)]
 pub struct ReachedGoalEvent;
 
+#[derive(Event)]
+pub struct GoalCollectedEvent;
+
 #[derive(Event)]
 pub struct HitTrapEvent;
 
 #[derive(Event)]
 pub struct PlayerMovedEvent;
 
+/// A player moved or control passed to another, see [`Outcome::is_move`]
+#[derive(Event)]
+pub struct MoveSpentEvent;
+
+#[derive(Event)]
+pub struct KeyPickedUpEvent;
+
+/// The player was sent from one teleporter to its partner
+#[derive(Event)]
+pub struct TeleportedEvent {
+    pub from: GridPos,
+    pub to: GridPos,
+}
+
 #[derive(Event)]
 pub struct TimerExpiredEvent;
 
-#[derive(Resource)]
-pub struct GameTimer(pub Option<Timer>);
+#[derive(Event)]
+pub struct RestartLevelEvent;
+
+/// The level being played, see [`GameState`]. The systems here only translate between it and Bevy.
+#[derive(Resource, Default)]
+pub struct Game(pub Option<GameState>);
+
+/// States before each move of the current level, most recent last
+#[derive(Resource, Default)]
+pub struct MoveHistory(pub Vec<GameState>);
+
+/// Moves spent in the current level, counted like move budgets and par.
+/// Undoing doesn't take moves back.
+#[derive(Resource, Default)]
+pub struct MoveCounter(pub u32);
+
+/// Whether the game is paused. Time stands still and moves are ignored until it's unpaused.
+#[derive(Resource, Default)]
+pub struct Paused(pub bool);
+
+/// Sends the events matching the outcomes of a game step
+#[derive(SystemParam)]
+struct OutcomeEvents<'w> {
+    moved: EventWriter<'w, PlayerMovedEvent>,
+    spent_move: EventWriter<'w, MoveSpentEvent>,
+    goal_collected: EventWriter<'w, GoalCollectedEvent>,
+    reached_goal: EventWriter<'w, ReachedGoalEvent>,
+    hit_trap: EventWriter<'w, HitTrapEvent>,
+    key_picked_up: EventWriter<'w, KeyPickedUpEvent>,
+    teleported: EventWriter<'w, TeleportedEvent>,
+    timer_expired: EventWriter<'w, TimerExpiredEvent>,
+}
+
+impl OutcomeEvents<'_> {
+    fn send(&mut self, outcomes: Vec<Outcome>) {
+        for outcome in outcomes {
+            if outcome.is_move() {
+                self.spent_move.send(MoveSpentEvent);
+            }
+            match outcome {
+                Outcome::PlayerMoved => self.moved.send(PlayerMovedEvent),
+                Outcome::PlayerSwitched => {}
+                Outcome::GoalCollected => self.goal_collected.send(GoalCollectedEvent),
+                Outcome::ReachedGoal => self.reached_goal.send(ReachedGoalEvent),
+                Outcome::HitTrap => self.hit_trap.send(HitTrapEvent),
+                Outcome::KeyPickedUp => self.key_picked_up.send(KeyPickedUpEvent),
+                Outcome::Teleported { from, to } => {
+                    self.teleported.send(TeleportedEvent { from, to })
+                }
+                Outcome::TimerExpired => self.timer_expired.send(TimerExpiredEvent),
+            }
+        }
+    }
+}
 
 impl Plugin for GameMechanicsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, movement_system)
-            .insert_resource(GameTimer(None))
-            .add_systems(Update, reach_goal)
-            .add_systems(Update, hit_trap)
-            .add_systems(Update, timer_expired)
+        app.add_systems(Update, movement_system.run_if(not_paused))
+            .init_resource::<Game>()
+            .init_resource::<MoveHistory>()
+            .init_resource::<MoveCounter>()
+            .init_resource::<LevelSize>()
+            .init_resource::<Paused>()
+            .add_systems(Update, switch_player_system.run_if(not_paused))
+            .add_systems(Update, undo_system.run_if(not_paused))
+            .add_systems(Update, restart_system)
+            .add_systems(Update, timer_system.run_if(not_paused))
+            .add_systems(Update, pause_system.before(movement_system))
+            .add_systems(
+                Update,
+                count_moves
+                    .after(movement_system)
+                    .after(switch_player_system),
+            )
+            .add_systems(
+                Update,
+                (
+                    sync_player_from_game,
+                    sync_goals_from_game,
+                    sync_traps_from_game,
+                    sync_blocks_from_game,
+                    sync_keys_from_game,
+                )
+                    .after(movement_system)
+                    .after(switch_player_system)
+                    .after(undo_system)
+                    .after(timer_system),
+            )
             .add_event::<HitTrapEvent>()
             .add_event::<ReachedGoalEvent>()
+            .add_event::<GoalCollectedEvent>()
             .add_event::<PlayerMovedEvent>()
-            .add_event::<TimerExpiredEvent>();
+            .add_event::<MoveSpentEvent>()
+            .add_event::<KeyPickedUpEvent>()
+            .add_event::<TeleportedEvent>()
+            .add_event::<TimerExpiredEvent>()
+            .add_event::<RestartLevelEvent>();
     }
 }
 
 fn movement_system(
-    mut q_player_pos: Query<&mut GridPos, With<Player>>,
-    keys: Res<Input<KeyCode>>,
-    mut ev_moved: EventWriter<PlayerMovedEvent>,
+    mut game: ResMut<Game>,
+    mut history: ResMut<MoveHistory>,
+    actions: Res<Input<InputAction>>,
+    mut outcome_events: OutcomeEvents,
 ) {
-    let mut pos = ok_or_return!(q_player_pos.get_single_mut());
+    let direction = if actions.just_pressed(InputAction::MoveUp) {
+        Direction::Up
+    } else if actions.just_pressed(InputAction::MoveLeft) {
+        Direction::Left
+    } else if actions.just_pressed(InputAction::MoveDown) {
+        Direction::Down
+    } else if actions.just_pressed(InputAction::MoveRight) {
+        Direction::Right
+    } else {
+        return;
+    };
 
-    let mut moved = false;
-    if keys.just_pressed(KeyCode::W) || keys.just_pressed(KeyCode::Up) {
-        if pos.y < GRID_SIZE_Y - 1 {
-            pos.y += 1;
-            moved = true;
-        }
-    } else if keys.just_pressed(KeyCode::A) || keys.just_pressed(KeyCode::Left) {
-        if pos.x > 0 {
-            pos.x -= 1;
-            moved = true;
-        }
-    } else if keys.just_pressed(KeyCode::S) || keys.just_pressed(KeyCode::Down) {
-        if pos.y > 0 {
-            pos.y -= 1;
-            moved = true;
-        }
-    } else if keys.just_pressed(KeyCode::D) || keys.just_pressed(KeyCode::Right) {
-        #[allow(clippy::collapsible_if)]
-        if pos.x < GRID_SIZE_X - 1 {
-            pos.x += 1;
-            moved = true;
-        }
+    let game = some_or_return!(&mut game.0);
+    let before = game.clone();
+    outcome_events.send(game.step(Action::Move(direction)));
+
+    if *game != before {
+        history.0.push(before);
+    }
+}
+
+fn switch_player_system(
+    mut game: ResMut<Game>,
+    mut history: ResMut<MoveHistory>,
+    actions: Res<Input<InputAction>>,
+    mut outcome_events: OutcomeEvents,
+) {
+    if !actions.just_pressed(InputAction::SwitchPlayer) {
+        return;
     }
 
-    if moved {
-        ev_moved.send(PlayerMovedEvent);
+    let game = some_or_return!(&mut game.0);
+    let before = game.clone();
+    outcome_events.send(game.step(Action::SwitchPlayer));
+
+    if *game != before {
+        history.0.push(before);
     }
 }
 
-fn reach_goal(
-    q_player_pos: Query<&GridPos, (With<Player>, Changed<GridPos>)>,
-    q_goal_pos: Query<&GridPos, (With<Goal>, Without<Player>)>,
-    mut ev_reached_goal: EventWriter<ReachedGoalEvent>,
+fn undo_system(
+    mut game: ResMut<Game>,
+    mut history: ResMut<MoveHistory>,
+    actions: Res<Input<InputAction>>,
 ) {
-    let player_pos = ok_or_return!(q_player_pos.get_single());
+    if !actions.just_pressed(InputAction::Undo) {
+        return;
+    }
 
-    for goal in q_goal_pos.iter() {
-        if player_pos == goal {
-            ev_reached_goal.send(ReachedGoalEvent);
-        }
+    let game = some_or_return!(&mut game.0);
+    let previous = some_or_return!(history.0.pop());
+    game.rewind(previous);
+}
+
+fn restart_system(
+    actions: Res<Input<InputAction>>,
+    mut ev_restart: EventWriter<RestartLevelEvent>,
+) {
+    if actions.just_pressed(InputAction::Restart) {
+        ev_restart.send(RestartLevelEvent);
+    }
+}
+
+fn pause_system(actions: Res<Input<InputAction>>, mut paused: ResMut<Paused>) {
+    if actions.just_pressed(InputAction::Pause) {
+        paused.0 = !paused.0;
     }
 }
 
-fn hit_trap(
-    q_player_pos: Query<&GridPos, (With<Player>, Changed<GridPos>)>,
-    q_trap_pos: Query<&GridPos, (With<Trap>, Without<Player>)>,
-    mut ev_hit_trap: EventWriter<HitTrapEvent>,
+fn not_paused(paused: Res<Paused>) -> bool {
+    !paused.0
+}
+
+pub fn count_moves(
+    mut ev_spent_move: EventReader<MoveSpentEvent>,
+    mut move_counter: ResMut<MoveCounter>,
 ) {
-    let player_pos = ok_or_return!(q_player_pos.get_single());
+    move_counter.0 += ev_spent_move.iter().count() as u32;
+}
 
-    for trap in q_trap_pos.iter() {
-        if player_pos == trap {
-            ev_hit_trap.send(HitTrapEvent);
-        }
+fn timer_system(mut game: ResMut<Game>, time: Res<Time>, mut outcome_events: OutcomeEvents) {
+    let game = some_or_return!(&mut game.0);
+    outcome_events.send(game.step(Action::Tick(time.delta())));
+}
+
+fn sync_player_from_game(
+    game: Res<Game>,
+    mut q_players: Query<
+        (
+            &ObjectIndex,
+            &mut GridPos,
+            &mut GameColor,
+            &mut Inventory,
+            &mut Controlled,
+        ),
+        With<Player>,
+    >,
+) {
+    let game = some_or_return!(&game.0);
+
+    // Only write on change, to keep change detection meaningful for rendering
+    for (index, mut pos, mut color, mut inventory, mut controlled) in q_players.iter_mut() {
+        let player = some_or_continue!(game.players.get(index.0));
+        pos.set_if_neq(player.pos);
+        color.set_if_neq(player.color);
+        inventory.set_if_neq(Inventory(game.held_keys().collect()));
+        controlled.set_if_neq(Controlled(game.active_player == Some(index.0)));
+    }
+}
+
+fn sync_goals_from_game(
+    game: Res<Game>,
+    mut q_goals: Query<(&ObjectIndex, &mut Collected), With<Goal>>,
+) {
+    let game = some_or_return!(&game.0);
+
+    for (index, mut collected) in q_goals.iter_mut() {
+        let is_collected = game.collected.get(index.0).copied().unwrap_or(false);
+        collected.set_if_neq(Collected(is_collected));
+    }
+}
+
+fn sync_traps_from_game(
+    game: Res<Game>,
+    mut q_traps: Query<
+        (
+            &ObjectIndex,
+            &mut GridPos,
+            &mut GameColor,
+            &mut Filled,
+            &mut Active,
+        ),
+        With<Trap>,
+    >,
+) {
+    let game = some_or_return!(&game.0);
+
+    for (index, mut pos, mut color, mut filled, mut active) in q_traps.iter_mut() {
+        let trap = some_or_continue!(game.traps.get(index.0));
+        pos.set_if_neq(trap.pos);
+        color.set_if_neq(trap.color);
+        filled.set_if_neq(Filled(trap.filled));
+        active.set_if_neq(Active(trap.active));
+    }
+}
+
+fn sync_blocks_from_game(
+    game: Res<Game>,
+    mut q_blocks: Query<(&ObjectIndex, &mut GridPos, &mut Filled), With<Block>>,
+) {
+    let game = some_or_return!(&game.0);
+
+    for (index, mut pos, mut filled) in q_blocks.iter_mut() {
+        let block = some_or_continue!(game.blocks.get(index.0));
+        pos.set_if_neq(block.pos);
+        filled.set_if_neq(Filled(game.sunk[index.0]));
     }
 }
 
-fn timer_expired(
-    mut timer_res: ResMut<GameTimer>,
-    time: Res<Time>,
-    mut ev_timer_expired: EventWriter<TimerExpiredEvent>,
+fn sync_keys_from_game(
+    game: Res<Game>,
+    mut q_keys: Query<(&ObjectIndex, &mut Collected), With<Key>>,
 ) {
-    let timer = some_or_return!(&mut timer_res.0);
-    timer.tick(time.delta());
+    let game = some_or_return!(&game.0);
 
-    if timer.finished() {
-        ev_timer_expired.send(TimerExpiredEvent);
-        timer_res.0 = None;
+    for (index, mut collected) in q_keys.iter_mut() {
+        let is_held = game.held.get(index.0).copied().unwrap_or(false);
+        collected.set_if_neq(Collected(is_held));
     }
 }
@@ -0,0 +1,1417 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::{
+    game_mechanics::{Direction, GameColor, GridPos, LevelSize},
+    level::Level,
+};
+
+/// Everything that can happen to a level in progress, independent of the engine.
+/// The same rules drive the game, the solver and anything else that needs to simulate a level.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct GameState {
+    pub rules: Rules,
+    pub size: LevelSize,
+    pub players: Vec<Object>,
+    /// Index in `players` of the only one that moves, `None` to move them all at once
+    pub active_player: Option<usize>,
+    pub goals: Vec<Object>,
+    pub win_condition: WinCondition,
+    /// Whether each of `goals` has been collected
+    pub collected: Vec<bool>,
+    pub traps: Vec<TrapState>,
+    /// Block movement, whatever their color
+    pub walls: Vec<Object>,
+    /// Give the player their color when stepped on
+    pub paint_tiles: Vec<Object>,
+    pub teleporters: Vec<TeleporterState>,
+    /// The player keeps sliding over these, whatever their color
+    pub ice: Vec<Object>,
+    /// Pushed by the player, one cell at a time
+    pub blocks: Vec<Object>,
+    /// Whether each of `blocks` has sunk into a trap, filling it
+    pub sunk: Vec<bool>,
+    pub keys: Vec<Object>,
+    /// Whether each of `keys` has been picked up
+    pub held: Vec<bool>,
+    /// Block movement until the player holds a key of their color
+    pub doors: Vec<Object>,
+    pub switches: Vec<SwitchState>,
+    /// Whether each group of traps has been flipped by its toggle switches
+    pub flipped: Vec<bool>,
+    /// `None` for levels without a timer, and for turn-based ones
+    pub time_left: Option<Duration>,
+    /// Moves left in turn-based levels, where the world only advances as the player moves
+    pub moves_left: Option<u32>,
+    pub status: Status,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Object {
+    pub pos: GridPos,
+    pub color: GameColor,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct TrapState {
+    pub pos: GridPos,
+    pub color: GameColor,
+    pub patrol: Option<Patrol>,
+    pub color_cycle: Option<ColorCycle>,
+    /// Filled by a block, after which it no longer moves or hurts
+    pub filled: bool,
+    /// Index of the group of traps switched on and off together
+    pub group: Option<usize>,
+    /// Switched off traps don't hurt
+    pub active: bool,
+}
+
+impl TrapState {
+    /// Whether the trap can hurt, being neither filled nor switched off
+    fn is_armed(&self) -> bool {
+        self.active && !self.filled
+    }
+}
+
+/// Switches a group of traps on and off
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct SwitchState {
+    pub pos: GridPos,
+    pub color: GameColor,
+    /// Index of the group of traps it switches
+    pub group: usize,
+    pub kind: SwitchKind,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SwitchKind {
+    /// Flips its traps every time the player steps on it
+    #[default]
+    Toggle,
+    /// Flips its traps for as long as the player or a block stands on it
+    PressurePlate,
+}
+
+/// Sends the player to its partner when stepped on
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct TeleporterState {
+    pub pos: GridPos,
+    pub color: GameColor,
+    /// Index of the other teleporter of the pair in [`GameState::teleporters`]
+    pub partner: usize,
+    /// Only teleports a player of exactly its color
+    pub color_locked: bool,
+}
+
+/// When something that changes by itself takes its steps
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Schedule {
+    /// Time between steps, `None` to step along with every player move
+    pub interval: Option<Duration>,
+    elapsed: Duration,
+}
+
+#[derive(Copy, Clone)]
+enum Advance {
+    PlayerMove,
+    Tick(Duration),
+}
+
+impl Schedule {
+    pub fn new(interval: Option<Duration>) -> Schedule {
+        Schedule {
+            interval,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Number of steps that are due. A zero interval never steps.
+    fn steps(&mut self, advance: Advance) -> u32 {
+        match (self.interval, advance) {
+            (None, Advance::PlayerMove) => 1,
+            (Some(interval), Advance::Tick(delta)) if !interval.is_zero() => {
+                self.elapsed += delta;
+                let steps = (self.elapsed.as_nanos() / interval.as_nanos()) as u32;
+                self.elapsed -= interval * steps;
+                steps
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// A route a trap keeps walking along
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Patrol {
+    /// Cells visited in turn, starting with the trap's initial position
+    pub route: Vec<GridPos>,
+    pub pattern: PatrolPattern,
+    pub schedule: Schedule,
+    index: usize,
+    backwards: bool,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PatrolPattern {
+    /// Jump back to the start after the last cell
+    #[default]
+    Loop,
+    /// Walk the route back and forth
+    PingPong,
+}
+
+impl Patrol {
+    pub fn new(route: Vec<GridPos>, pattern: PatrolPattern, schedule: Schedule) -> Patrol {
+        Patrol {
+            route,
+            pattern,
+            schedule,
+            index: 0,
+            backwards: false,
+        }
+    }
+
+    /// Moves on to the next cell of the route and returns it
+    fn advance(&mut self) -> GridPos {
+        let last = self.route.len() - 1;
+
+        match self.pattern {
+            PatrolPattern::Loop => self.index = (self.index + 1) % self.route.len(),
+            PatrolPattern::PingPong if last > 0 => {
+                if self.index == last {
+                    self.backwards = true;
+                } else if self.index == 0 {
+                    self.backwards = false;
+                }
+                if self.backwards {
+                    self.index -= 1;
+                } else {
+                    self.index += 1;
+                }
+            }
+            PatrolPattern::PingPong => {}
+        }
+
+        self.route[self.index]
+    }
+}
+
+/// A sequence of colors a trap keeps going through
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ColorCycle {
+    pub colors: Vec<GameColor>,
+    pub schedule: Schedule,
+    index: usize,
+}
+
+impl ColorCycle {
+    /// Starts at the color `phase` steps into the sequence
+    pub fn new(colors: Vec<GameColor>, phase: usize, schedule: Schedule) -> ColorCycle {
+        ColorCycle {
+            index: phase % colors.len(),
+            colors,
+            schedule,
+        }
+    }
+
+    pub fn color(&self) -> GameColor {
+        self.colors[self.index]
+    }
+
+    fn advance(&mut self) -> GameColor {
+        self.index = (self.index + 1) % self.colors.len();
+        self.color()
+    }
+}
+
+/// How the colors of the player and of what they run into interact, chosen per level pack
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default, Deserialize)]
+pub struct Rules {
+    #[serde(default)]
+    pub traps: TrapRule,
+    #[serde(default)]
+    pub goals: GoalRule,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrapRule {
+    /// Every trap hurts, whatever its color
+    #[default]
+    Always,
+    /// A trap only hurts a player whose color shares a channel with it
+    SharedChannel,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GoalRule {
+    /// Every goal can be reached, whatever its color
+    #[default]
+    Any,
+    /// A goal only accepts a player whose color shares a channel with it
+    SharedChannel,
+    /// A goal only accepts a player of exactly its color
+    Matching,
+}
+
+impl TrapRule {
+    pub fn hurts(self, trap: GameColor, player: GameColor) -> bool {
+        match self {
+            TrapRule::Always => true,
+            TrapRule::SharedChannel => trap.shares_channel_with(player),
+        }
+    }
+}
+
+impl GoalRule {
+    pub fn accepts(self, goal: GameColor, player: GameColor) -> bool {
+        match self {
+            GoalRule::Any => true,
+            GoalRule::SharedChannel => goal.shares_channel_with(player),
+            GoalRule::Matching => goal == player,
+        }
+    }
+}
+
+/// What it takes to win a level
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WinCondition {
+    /// Reaching any one goal
+    #[default]
+    AnyGoal,
+    /// Collecting every goal, in any order
+    AllGoals,
+    /// Collecting every goal, in the order they are listed
+    GoalsInOrder,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Status {
+    Playing,
+    Won,
+    Lost,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Action {
+    Move(Direction),
+    /// Hands control to the next player, in levels where only one moves at a time
+    SwitchPlayer,
+    Tick(Duration),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    PlayerMoved,
+    /// Control passed to another player
+    PlayerSwitched,
+    /// A goal was collected, but more are needed to win
+    GoalCollected,
+    ReachedGoal,
+    HitTrap,
+    KeyPickedUp,
+    Teleported {
+        from: GridPos,
+        to: GridPos,
+    },
+    /// The time limit or the move budget ran out
+    TimerExpired,
+}
+
+impl Outcome {
+    /// Whether the outcome spends a move, for move budgets, the move counter and par alike.
+    /// Moves that a wall or the edge stop don't count.
+    pub fn is_move(self) -> bool {
+        matches!(self, Outcome::PlayerMoved | Outcome::PlayerSwitched)
+    }
+}
+
+impl GameState {
+    pub fn new(level: &Level, rules: Rules) -> GameState {
+        // Turn-based levels leave nothing to real time, everything steps along with moves
+        let turn_based = level.move_limit.is_some();
+        let interval = |every_seconds: Option<f32>| {
+            every_seconds
+                .filter(|_| !turn_based)
+                .map(Duration::from_secs_f32)
+        };
+
+        // Groups are named in the level, but referred to by index from here on
+        let mut groups: Vec<&str> = vec![];
+        for group in level
+            .switches
+            .iter()
+            .map(|switch| switch.group.as_str())
+            .chain(level.traps.iter().filter_map(|trap| trap.group.as_deref()))
+        {
+            if !groups.contains(&group) {
+                groups.push(group);
+            }
+        }
+        let group_index = |group: &str| groups.iter().position(|&g| g == group);
+
+        let mut state = GameState {
+            rules,
+            size: LevelSize {
+                width: level.width,
+                height: level.height,
+            },
+            players: level
+                .players
+                .iter()
+                .map(|player| Object {
+                    pos: GridPos {
+                        x: player.x,
+                        y: player.y,
+                    },
+                    color: player.color,
+                })
+                .collect(),
+            active_player: level.players.iter().position(|player| player.active),
+            goals: level
+                .goals
+                .iter()
+                .map(|goal| Object {
+                    pos: GridPos {
+                        x: goal.x,
+                        y: goal.y,
+                    },
+                    color: goal.color,
+                })
+                .collect(),
+            win_condition: level.win_condition,
+            collected: vec![false; level.goals.len()],
+            traps: level
+                .traps
+                .iter()
+                .map(|trap| {
+                    let pos = GridPos {
+                        x: trap.x,
+                        y: trap.y,
+                    };
+                    let patrol = trap.patrol.as_ref().map(|patrol| {
+                        let route = std::iter::once(pos)
+                            .chain(patrol.path.iter().map(|p| GridPos { x: p.x, y: p.y }))
+                            .collect();
+                        let schedule = Schedule::new(interval(patrol.every_seconds));
+                        Patrol::new(route, patrol.pattern, schedule)
+                    });
+                    let color_cycle = trap.color_cycle.as_ref().map(|cycle| {
+                        let schedule = Schedule::new(interval(cycle.every_seconds));
+                        ColorCycle::new(cycle.colors.clone(), cycle.phase, schedule)
+                    });
+
+                    TrapState {
+                        pos,
+                        color: color_cycle.as_ref().map_or(trap.color, ColorCycle::color),
+                        patrol,
+                        color_cycle,
+                        filled: false,
+                        group: trap.group.as_deref().and_then(group_index),
+                        active: true,
+                    }
+                })
+                .collect(),
+            walls: level
+                .walls
+                .iter()
+                .map(|wall| Object {
+                    pos: GridPos {
+                        x: wall.x,
+                        y: wall.y,
+                    },
+                    color: wall.color,
+                })
+                .collect(),
+            paint_tiles: level
+                .paint_tiles
+                .iter()
+                .map(|tile| Object {
+                    pos: GridPos {
+                        x: tile.x,
+                        y: tile.y,
+                    },
+                    color: tile.color,
+                })
+                .collect(),
+            teleporters: level
+                .teleporters
+                .iter()
+                .enumerate()
+                .map(|(index, teleporter)| TeleporterState {
+                    pos: GridPos {
+                        x: teleporter.x,
+                        y: teleporter.y,
+                    },
+                    color: teleporter.color,
+                    // Validation makes sure there is exactly one, but a lone teleporter goes nowhere
+                    partner: level
+                        .teleporters
+                        .iter()
+                        .enumerate()
+                        .position(|(other_index, other)| {
+                            other_index != index && other.pair == teleporter.pair
+                        })
+                        .unwrap_or(index),
+                    color_locked: teleporter.color_locked,
+                })
+                .collect(),
+            ice: level
+                .ice
+                .iter()
+                .map(|ice| Object {
+                    pos: GridPos { x: ice.x, y: ice.y },
+                    color: ice.color,
+                })
+                .collect(),
+            blocks: level
+                .blocks
+                .iter()
+                .map(|block| Object {
+                    pos: GridPos {
+                        x: block.x,
+                        y: block.y,
+                    },
+                    color: block.color,
+                })
+                .collect(),
+            sunk: vec![false; level.blocks.len()],
+            keys: level
+                .keys
+                .iter()
+                .map(|key| Object {
+                    pos: GridPos { x: key.x, y: key.y },
+                    color: key.color,
+                })
+                .collect(),
+            held: vec![false; level.keys.len()],
+            doors: level
+                .doors
+                .iter()
+                .map(|door| Object {
+                    pos: GridPos {
+                        x: door.x,
+                        y: door.y,
+                    },
+                    color: door.color,
+                })
+                .collect(),
+            switches: level
+                .switches
+                .iter()
+                .map(|switch| SwitchState {
+                    pos: GridPos {
+                        x: switch.x,
+                        y: switch.y,
+                    },
+                    color: switch.color,
+                    group: group_index(&switch.group).unwrap_or_default(),
+                    kind: switch.kind,
+                })
+                .collect(),
+            flipped: vec![false; groups.len()],
+            time_left: level
+                .time_limit
+                .filter(|_| !turn_based)
+                .map(Duration::from_secs_f32),
+            moves_left: level.move_limit,
+            status: Status::Playing,
+        };
+        // Blocks and players can start out on pressure plates
+        state.switch_traps();
+        state
+    }
+
+    /// Applies an action, returning what happened as a result.
+    /// Once the level is won or lost, actions have no effect.
+    pub fn step(&mut self, action: Action) -> Vec<Outcome> {
+        if self.status != Status::Playing {
+            return vec![];
+        }
+
+        match action {
+            Action::Move(direction) => self.move_players(direction),
+            Action::SwitchPlayer => self.switch_player(),
+            Action::Tick(delta) => self.tick(delta),
+        }
+    }
+
+    /// Rewinds to an earlier state of the same level, for undoing moves.
+    /// Time keeps running and moves stay spent, so undoing can't be used to win more of either.
+    pub fn rewind(&mut self, earlier: GameState) {
+        if self.status != Status::Playing {
+            return;
+        }
+
+        let (time_left, moves_left) = (self.time_left, self.moves_left);
+        *self = earlier;
+        self.time_left = time_left;
+        self.moves_left = moves_left;
+        self.switch_traps();
+    }
+
+    /// Moves the active player, or every player the same way,
+    /// front-most first so they can follow each other
+    fn move_players(&mut self, direction: Direction) -> Vec<Outcome> {
+        let old_positions: Vec<GridPos> = self.players.iter().map(|player| player.pos).collect();
+
+        let mut order: Vec<usize> = match self.active_player {
+            Some(active) => vec![active],
+            None => (0..self.players.len()).collect(),
+        };
+        order.sort_by_key(|&index| {
+            let pos = old_positions[index];
+            let ahead = pos.step(direction);
+            -((ahead.x - pos.x) * pos.x + (ahead.y - pos.y) * pos.y)
+        });
+
+        let mut outcomes = vec![];
+        for index in order {
+            outcomes.extend(self.move_player(index, direction));
+            if self.status != Status::Playing {
+                break;
+            }
+        }
+
+        let moved = self
+            .players
+            .iter()
+            .zip(&old_positions)
+            .any(|(player, &old_pos)| player.pos != old_pos);
+        if !moved {
+            return outcomes;
+        }
+        outcomes.insert(0, Outcome::PlayerMoved);
+        if self.status != Status::Playing {
+            return outcomes;
+        }
+
+        self.switch_traps();
+        let old_trap_positions = self.advance_traps(Advance::PlayerMove);
+
+        if self.hits_trap(&old_positions, &old_trap_positions) {
+            self.status = Status::Lost;
+            outcomes.push(Outcome::HitTrap);
+        } else {
+            outcomes.extend(self.reach_goals());
+        }
+
+        outcomes.extend(self.spend_move());
+        outcomes
+    }
+
+    fn switch_player(&mut self) -> Vec<Outcome> {
+        match &mut self.active_player {
+            Some(active) if self.players.len() > 1 => {
+                *active = (*active + 1) % self.players.len();
+                let mut outcomes = vec![Outcome::PlayerSwitched];
+                outcomes.extend(self.spend_move());
+                outcomes
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Takes a move out of the budget of turn-based levels, losing once it runs out
+    fn spend_move(&mut self) -> Option<Outcome> {
+        let moves_left = self.moves_left.as_mut()?;
+        *moves_left = moves_left.saturating_sub(1);
+        if *moves_left == 0 && self.status == Status::Playing {
+            self.status = Status::Lost;
+            return Some(Outcome::TimerExpired);
+        }
+        None
+    }
+
+    /// Moves one player, returning what they met on the way
+    fn move_player(&mut self, index: usize, direction: Direction) -> Vec<Outcome> {
+        let mut outcomes = vec![];
+        let mut next_pos = self.players[index].pos.step(direction);
+
+        if let Some(block) = self.block_at(next_pos) {
+            if !self.push_block(block, direction) {
+                return outcomes;
+            }
+        }
+
+        // Keep sliding while on ice, meeting whatever lies along the way
+        while self.is_free(next_pos) {
+            self.players[index].pos = next_pos;
+
+            outcomes.extend(self.pick_up_key(index));
+            self.press_toggle_switch(index);
+            let teleported = self.enter_cell(index);
+            outcomes.extend(teleported);
+
+            next_pos = self.players[index].pos.step(direction);
+            if teleported.is_some() || !self.is_on_ice(index) || !self.is_free(next_pos) {
+                break;
+            }
+
+            if self.hit_trap_in_passing(index) {
+                self.status = Status::Lost;
+                outcomes.push(Outcome::HitTrap);
+                return outcomes;
+            }
+            if !self.needs_every_player_on_a_goal() {
+                outcomes.extend(self.reach_goal(index));
+                if self.status != Status::Playing {
+                    return outcomes;
+                }
+            }
+        }
+
+        outcomes
+    }
+
+    fn is_free(&self, pos: GridPos) -> bool {
+        self.size.contains(pos)
+            && !self.walls.iter().any(|wall| wall.pos == pos)
+            && !self.is_occupied(pos)
+            && !self
+                .doors
+                .iter()
+                .any(|door| door.pos == pos && !self.holds_key(door.color))
+    }
+
+    /// Whether a player or a block stands on the cell
+    fn is_occupied(&self, pos: GridPos) -> bool {
+        self.block_at(pos).is_some() || self.players.iter().any(|player| player.pos == pos)
+    }
+
+    pub fn holds_key(&self, color: GameColor) -> bool {
+        self.held_keys().any(|key| key == color)
+    }
+
+    /// Colors of the keys picked up so far, shared by all players
+    pub fn held_keys(&self) -> impl Iterator<Item = GameColor> + '_ {
+        self.keys
+            .iter()
+            .zip(&self.held)
+            .filter(|(_, &held)| held)
+            .map(|(key, _)| key.color)
+    }
+
+    /// Flips the groups of the toggle switches under the player, right away so that traps further
+    /// along a slide are already switched
+    fn press_toggle_switch(&mut self, index: usize) {
+        let pos = self.players[index].pos;
+        let mut pressed = false;
+        for switch in &self.switches {
+            if switch.pos == pos && switch.kind == SwitchKind::Toggle {
+                self.flipped[switch.group] = !self.flipped[switch.group];
+                pressed = true;
+            }
+        }
+        if pressed {
+            self.switch_traps();
+        }
+    }
+
+    /// Switches each group of traps on or off, from its toggle switches and pressure plates
+    fn switch_traps(&mut self) {
+        let mut flipped = self.flipped.clone();
+        for switch in &self.switches {
+            if switch.kind == SwitchKind::PressurePlate && self.is_occupied(switch.pos) {
+                flipped[switch.group] = !self.flipped[switch.group];
+            }
+        }
+
+        for trap in &mut self.traps {
+            if let Some(group) = trap.group {
+                trap.active = !flipped[group];
+            }
+        }
+    }
+
+    fn pick_up_key(&mut self, index: usize) -> Option<Outcome> {
+        let pos = self.players[index].pos;
+        let key = self
+            .keys
+            .iter()
+            .zip(&self.held)
+            .position(|(key, &held)| !held && key.pos == pos)?;
+        self.held[key] = true;
+        Some(Outcome::KeyPickedUp)
+    }
+
+    fn block_at(&self, pos: GridPos) -> Option<usize> {
+        self.blocks
+            .iter()
+            .zip(&self.sunk)
+            .position(|(block, &sunk)| !sunk && block.pos == pos)
+    }
+
+    /// Pushes a block one cell, returning whether it moved.
+    /// A block pushed onto a trap that would hurt its color sinks into it, filling it;
+    /// any other trap stops it.
+    fn push_block(&mut self, index: usize, direction: Direction) -> bool {
+        let block = self.blocks[index];
+        let target = block.pos.step(direction);
+        if !self.is_free(target) {
+            return false;
+        }
+
+        if let Some(trap) = self
+            .traps
+            .iter_mut()
+            .find(|trap| trap.is_armed() && trap.pos == target)
+        {
+            if !self.rules.traps.hurts(trap.color, block.color) {
+                return false;
+            }
+            trap.filled = true;
+            self.sunk[index] = true;
+        }
+
+        self.blocks[index].pos = target;
+        true
+    }
+
+    fn is_on_ice(&self, index: usize) -> bool {
+        let pos = self.players[index].pos;
+        self.ice.iter().any(|ice| ice.pos == pos)
+    }
+
+    /// Applies the paint tile or teleporter the player stands on
+    fn enter_cell(&mut self, index: usize) -> Option<Outcome> {
+        let pos = self.players[index].pos;
+
+        if let Some(tile) = self.paint_tiles.iter().find(|tile| tile.pos == pos) {
+            self.players[index].color = tile.color;
+        }
+
+        let teleporter = self.teleporters.iter().find(|t| t.pos == pos)?;
+        if teleporter.color_locked && teleporter.color != self.players[index].color {
+            return None;
+        }
+        let to = self.teleporters[teleporter.partner].pos;
+        if self.is_occupied(to) {
+            return None;
+        }
+        self.players[index].pos = to;
+        Some(Outcome::Teleported { from: pos, to })
+    }
+
+    /// Whether a trap that hurts the player stands where they slide through
+    fn hit_trap_in_passing(&self, index: usize) -> bool {
+        let player = self.players[index];
+        self.traps.iter().any(|trap| {
+            trap.is_armed()
+                && trap.pos == player.pos
+                && self.rules.traps.hurts(trap.color, player.color)
+        })
+    }
+
+    /// The goal the player stands on, if it accepts them.
+    /// With several players, each needs a goal of exactly their color, whatever the rules.
+    fn goal_under(&self, index: usize) -> Option<usize> {
+        let player = self.players[index];
+        let several_players = self.players.len() > 1;
+        self.goals.iter().position(|goal| {
+            let accepts = if several_players {
+                goal.color == player.color
+            } else {
+                self.rules.goals.accepts(goal.color, player.color)
+            };
+            goal.pos == player.pos && accepts
+        })
+    }
+
+    /// Collects the goal the player stands on, if it accepts them
+    fn reach_goal(&mut self, index: usize) -> Option<Outcome> {
+        let goal = self.goal_under(index)?;
+        self.collect_goal(goal)
+    }
+
+    /// With several players, reaching any goal means every player standing on one at once
+    fn needs_every_player_on_a_goal(&self) -> bool {
+        self.players.len() > 1 && self.win_condition == WinCondition::AnyGoal
+    }
+
+    /// Collects the goals the players stand on
+    fn reach_goals(&mut self) -> Vec<Outcome> {
+        if self.needs_every_player_on_a_goal() {
+            if (0..self.players.len()).all(|index| self.goal_under(index).is_some()) {
+                self.status = Status::Won;
+                return vec![Outcome::ReachedGoal];
+            }
+            return vec![];
+        }
+
+        let mut outcomes = vec![];
+        for index in 0..self.players.len() {
+            outcomes.extend(self.reach_goal(index));
+            if self.status != Status::Playing {
+                break;
+            }
+        }
+        outcomes
+    }
+
+    fn collect_goal(&mut self, index: usize) -> Option<Outcome> {
+        let can_collect = match self.win_condition {
+            WinCondition::AnyGoal | WinCondition::AllGoals => !self.collected[index],
+            WinCondition::GoalsInOrder => {
+                self.collected.iter().position(|&collected| !collected) == Some(index)
+            }
+        };
+        if !can_collect {
+            return None;
+        }
+
+        self.collected[index] = true;
+        if self.win_condition == WinCondition::AnyGoal || self.collected.iter().all(|&c| c) {
+            self.status = Status::Won;
+            Some(Outcome::ReachedGoal)
+        } else {
+            Some(Outcome::GoalCollected)
+        }
+    }
+
+    /// Lets traps patrol and change color, returning where all traps were before
+    fn advance_traps(&mut self, advance: Advance) -> Vec<GridPos> {
+        self.traps
+            .iter_mut()
+            .map(|trap| {
+                let old_pos = trap.pos;
+                if trap.filled {
+                    return old_pos;
+                }
+                if let Some(patrol) = &mut trap.patrol {
+                    for _ in 0..patrol.schedule.steps(advance) {
+                        trap.pos = patrol.advance();
+                    }
+                }
+                if let Some(color_cycle) = &mut trap.color_cycle {
+                    for _ in 0..color_cycle.schedule.steps(advance) {
+                        trap.color = color_cycle.advance();
+                    }
+                }
+                old_pos
+            })
+            .collect()
+    }
+
+    /// Whether any player ends up on a trap that hurts them, or swaps cells with one
+    fn hits_trap(&self, old_player_positions: &[GridPos], old_trap_positions: &[GridPos]) -> bool {
+        self.players
+            .iter()
+            .zip(old_player_positions)
+            .any(|(player, &old_player_pos)| {
+                self.traps
+                    .iter()
+                    .zip(old_trap_positions)
+                    .any(|(trap, &old_trap_pos)| {
+                        let collides = trap.pos == player.pos
+                            || (trap.pos == old_player_pos && old_trap_pos == player.pos);
+                        collides
+                            && trap.is_armed()
+                            && self.rules.traps.hurts(trap.color, player.color)
+                    })
+            })
+    }
+
+    pub fn goals_collected(&self) -> usize {
+        self.collected
+            .iter()
+            .filter(|&&collected| collected)
+            .count()
+    }
+
+    fn tick(&mut self, delta: Duration) -> Vec<Outcome> {
+        let old_trap_positions = self.advance_traps(Advance::Tick(delta));
+
+        let player_positions: Vec<GridPos> = self.players.iter().map(|player| player.pos).collect();
+        if self.hits_trap(&player_positions, &old_trap_positions) {
+            self.status = Status::Lost;
+            return vec![Outcome::HitTrap];
+        }
+
+        let time_left = match &mut self.time_left {
+            Some(time_left) => time_left,
+            None => return vec![],
+        };
+
+        *time_left = time_left.saturating_sub(delta);
+        if !time_left.is_zero() {
+            return vec![];
+        }
+
+        self.status = Status::Lost;
+        vec![Outcome::TimerExpired]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_support::test_level;
+
+    #[test]
+    fn swapping_cells_with_a_patrolling_trap_hits_it() {
+        let level = test_level(
+            3,
+            1,
+            json!({
+                "goals": [{ "x": 2, "y": 0, "color": "White" }],
+                "traps": [{
+                    "x": 1, "y": 0, "color": "Red",
+                    "patrol": { "path": [{ "x": 0, "y": 0 }], "every_seconds": null },
+                }],
+            }),
+        );
+        let mut state = GameState::new(&level, Rules::default());
+
+        let outcomes = state.step(Action::Move(Direction::Right));
+
+        assert_eq!(outcomes, vec![Outcome::PlayerMoved, Outcome::HitTrap]);
+        assert_eq!(state.traps[0].pos, GridPos { x: 0, y: 0 });
+    }
+
+    #[test]
+    fn color_cycles_start_at_their_phase_and_step_with_moves_or_time() {
+        let level = |every_seconds: Option<f32>| {
+            test_level(
+                4,
+                2,
+                json!({
+                    "traps": [{
+                        "x": 3, "y": 0, "color": "White",
+                        "color_cycle": {
+                            "colors": ["Red", "Green", "Blue"],
+                            "phase": 1,
+                            "every_seconds": every_seconds,
+                        },
+                    }],
+                }),
+            )
+        };
+        let color = |state: &GameState| state.traps[0].color;
+
+        // Every move, wrapping around, and time doesn't matter
+        let mut state = GameState::new(&level(None), Rules::default());
+        assert_eq!(color(&state), GameColor::Green);
+        state.step(Action::Move(Direction::Up));
+        assert_eq!(color(&state), GameColor::Blue);
+        state.step(Action::Tick(Duration::from_secs(5)));
+        assert_eq!(color(&state), GameColor::Blue);
+        state.step(Action::Move(Direction::Down));
+        assert_eq!(color(&state), GameColor::Red);
+
+        // Every second, carrying what's left of a tick over to the next, and moves don't matter
+        let mut state = GameState::new(&level(Some(1.0)), Rules::default());
+        state.step(Action::Move(Direction::Up));
+        state.step(Action::Tick(Duration::from_millis(600)));
+        assert_eq!(color(&state), GameColor::Green);
+        state.step(Action::Tick(Duration::from_millis(600)));
+        assert_eq!(color(&state), GameColor::Blue);
+        state.step(Action::Tick(Duration::from_millis(2300)));
+        assert_eq!(color(&state), GameColor::Green);
+        state.step(Action::Tick(Duration::from_millis(500)));
+        assert_eq!(color(&state), GameColor::Blue);
+    }
+
+    #[test]
+    fn zero_intervals_never_step() {
+        let mut schedule = Schedule::new(Some(Duration::ZERO));
+        assert_eq!(schedule.steps(Advance::Tick(Duration::from_secs(1))), 0);
+        assert_eq!(schedule.steps(Advance::PlayerMove), 0);
+    }
+
+    #[test]
+    fn teleporters_only_send_matching_players_when_color_locked() {
+        let level = |color_locked: bool| {
+            test_level(
+                4,
+                1,
+                json!({
+                    "player": { "x": 0, "y": 0, "color": "Blue" },
+                    "teleporters": [
+                        { "x": 1, "y": 0, "color": "Red", "pair": "a", "color_locked": color_locked },
+                        { "x": 2, "y": 0, "color": "Red", "pair": "a", "color_locked": color_locked },
+                    ],
+                }),
+            )
+        };
+
+        let mut state = GameState::new(&level(false), Rules::default());
+        let outcomes = state.step(Action::Move(Direction::Right));
+        let (from, to) = (GridPos { x: 1, y: 0 }, GridPos { x: 2, y: 0 });
+        assert_eq!(
+            outcomes,
+            vec![Outcome::PlayerMoved, Outcome::Teleported { from, to }]
+        );
+        assert_eq!(state.players[0].pos, to);
+
+        let mut state = GameState::new(&level(true), Rules::default());
+        assert_eq!(
+            state.step(Action::Move(Direction::Right)),
+            vec![Outcome::PlayerMoved]
+        );
+        assert_eq!(state.players[0].pos, from);
+    }
+
+    #[test]
+    fn ice_slides_the_player_past_goals_and_into_traps() {
+        let level = |traps: serde_json::Value| {
+            test_level(
+                5,
+                2,
+                json!({
+                    "win_condition": "all_goals",
+                    "goals": [{ "x": 2, "y": 0, "color": "White" }, { "x": 0, "y": 1, "color": "White" }],
+                    "traps": traps,
+                    "ice": [
+                        { "x": 1, "y": 0, "color": "Blue" },
+                        { "x": 2, "y": 0, "color": "Blue" },
+                        { "x": 3, "y": 0, "color": "Blue" },
+                    ],
+                }),
+            )
+        };
+
+        let mut state = GameState::new(&level(json!([])), Rules::default());
+        let outcomes = state.step(Action::Move(Direction::Right));
+        assert_eq!(outcomes, vec![Outcome::PlayerMoved, Outcome::GoalCollected]);
+        assert_eq!(state.players[0].pos, GridPos { x: 4, y: 0 });
+
+        let traps = json!([{ "x": 3, "y": 0, "color": "Red" }]);
+        let mut state = GameState::new(&level(traps), Rules::default());
+        let outcomes = state.step(Action::Move(Direction::Right));
+        assert_eq!(
+            outcomes,
+            vec![
+                Outcome::PlayerMoved,
+                Outcome::GoalCollected,
+                Outcome::HitTrap
+            ]
+        );
+        assert_eq!(state.players[0].pos, GridPos { x: 3, y: 0 });
+    }
+
+    #[test]
+    fn blocks_are_pushed_until_blocked_and_sink_into_traps() {
+        let level = test_level(
+            4,
+            2,
+            json!({
+                "goals": [{ "x": 3, "y": 1, "color": "White" }],
+                "traps": [{ "x": 2, "y": 1, "color": "Red" }],
+                "blocks": [
+                    { "x": 1, "y": 0, "color": "Blue" },
+                    { "x": 2, "y": 0, "color": "Blue" },
+                    { "x": 1, "y": 1, "color": "Blue" },
+                ],
+            }),
+        );
+        let mut state = GameState::new(&level, Rules::default());
+
+        // Two blocks in a row can't be pushed
+        assert_eq!(state.step(Action::Move(Direction::Right)), vec![]);
+
+        // A block pushed onto a trap fills it, clearing the way
+        state.step(Action::Move(Direction::Up));
+        state.step(Action::Move(Direction::Right));
+        assert_eq!(state.players[0].pos, GridPos { x: 1, y: 1 });
+        assert_eq!(state.blocks[2].pos, GridPos { x: 2, y: 1 });
+        assert!(state.sunk[2] && state.traps[0].filled);
+
+        state.step(Action::Move(Direction::Right));
+        assert_eq!(state.status, Status::Playing);
+        let outcomes = state.step(Action::Move(Direction::Right));
+        assert_eq!(outcomes, vec![Outcome::PlayerMoved, Outcome::ReachedGoal]);
+    }
+
+    #[test]
+    fn doors_only_open_for_held_keys_of_their_color() {
+        let level = test_level(
+            3,
+            2,
+            json!({
+                "keys": [{ "x": 0, "y": 1, "color": "Green" }],
+                "doors": [{ "x": 1, "y": 0, "color": "Green" }],
+            }),
+        );
+        let mut state = GameState::new(&level, Rules::default());
+
+        assert_eq!(state.step(Action::Move(Direction::Right)), vec![]);
+        assert_eq!(
+            state.step(Action::Move(Direction::Up)),
+            vec![Outcome::PlayerMoved, Outcome::KeyPickedUp]
+        );
+        assert!(state.holds_key(GameColor::Green));
+
+        state.step(Action::Move(Direction::Down));
+        state.step(Action::Move(Direction::Right));
+        assert_eq!(state.players[0].pos, GridPos { x: 1, y: 0 });
+    }
+
+    #[test]
+    fn switches_turn_their_group_of_traps_off() {
+        let level = |kind: &str| {
+            test_level(
+                3,
+                2,
+                json!({
+                    "traps": [
+                        { "x": 1, "y": 0, "color": "Red", "group": "a" },
+                        { "x": 1, "y": 1, "color": "Red", "group": "a" },
+                    ],
+                    "switches": [{ "x": 0, "y": 1, "color": "Red", "group": "a", "kind": kind }],
+                }),
+            )
+        };
+
+        // A toggle switch stays flipped once the player steps off it
+        let mut state = GameState::new(&level("toggle"), Rules::default());
+        state.step(Action::Move(Direction::Up));
+        assert!(state.traps.iter().all(|trap| !trap.active));
+        state.step(Action::Move(Direction::Down));
+        assert_eq!(
+            state.step(Action::Move(Direction::Right)),
+            vec![Outcome::PlayerMoved]
+        );
+
+        // A pressure plate only while it is stood on
+        let mut state = GameState::new(&level("pressure_plate"), Rules::default());
+        state.step(Action::Move(Direction::Up));
+        assert!(state.traps.iter().all(|trap| !trap.active));
+        state.step(Action::Move(Direction::Down));
+        assert_eq!(
+            state.step(Action::Move(Direction::Right)),
+            vec![Outcome::PlayerMoved, Outcome::HitTrap]
+        );
+    }
+
+    #[test]
+    fn pressure_plates_held_down_from_the_start_switch_their_traps() {
+        let level = test_level(
+            3,
+            2,
+            json!({
+                "goals": [{ "x": 2, "y": 0, "color": "White" }],
+                "traps": [{ "x": 1, "y": 0, "color": "Red", "group": "a" }],
+                "blocks": [{ "x": 0, "y": 1, "color": "Blue" }],
+                "switches": [{ "x": 0, "y": 1, "color": "Red", "group": "a", "kind": "pressure_plate" }],
+            }),
+        );
+        let mut state = GameState::new(&level, Rules::default());
+
+        assert!(!state.traps[0].active);
+        assert_eq!(
+            state.step(Action::Move(Direction::Right)),
+            vec![Outcome::PlayerMoved]
+        );
+    }
+
+    #[test]
+    fn toggle_switches_slid_over_switch_traps_further_along() {
+        let level = test_level(
+            6,
+            1,
+            json!({
+                "goals": [{ "x": 5, "y": 0, "color": "White" }],
+                "traps": [{ "x": 2, "y": 0, "color": "Red", "group": "a" }],
+                "switches": [{ "x": 1, "y": 0, "color": "Red", "group": "a", "kind": "toggle" }],
+                "ice": [
+                    { "x": 1, "y": 0, "color": "Blue" },
+                    { "x": 2, "y": 0, "color": "Blue" },
+                    { "x": 3, "y": 0, "color": "Blue" },
+                ],
+            }),
+        );
+        let mut state = GameState::new(&level, Rules::default());
+
+        assert_eq!(
+            state.step(Action::Move(Direction::Right)),
+            vec![Outcome::PlayerMoved]
+        );
+        assert_eq!(state.players[0].pos, GridPos { x: 4, y: 0 });
+    }
+
+    #[test]
+    fn players_move_together_and_win_on_goals_at_once() {
+        let level = |goals: [&str; 2]| {
+            test_level(
+                4,
+                2,
+                json!({
+                    "players": [
+                        { "x": 0, "y": 0, "color": "Red" },
+                        { "x": 1, "y": 0, "color": "Blue" },
+                    ],
+                    "goals": [
+                        { "x": 2, "y": 0, "color": goals[0] },
+                        { "x": 3, "y": 0, "color": goals[1] },
+                    ],
+                }),
+            )
+        };
+
+        // Even though the rules let any goal be reached, each player needs one of their color
+        let mut state = GameState::new(&level(["Blue", "Red"]), Rules::default());
+        state.step(Action::Move(Direction::Right));
+        assert_eq!(
+            state.step(Action::Move(Direction::Right)),
+            vec![Outcome::PlayerMoved]
+        );
+        assert_eq!(state.status, Status::Playing);
+
+        let mut state = GameState::new(&level(["Red", "Blue"]), Rules::default());
+
+        // The player in front moves first, making room for the one behind
+        assert_eq!(
+            state.step(Action::Move(Direction::Right)),
+            vec![Outcome::PlayerMoved]
+        );
+        assert_eq!(state.players[0].pos, GridPos { x: 1, y: 0 });
+        assert_eq!(
+            state.step(Action::Move(Direction::Right)),
+            vec![Outcome::PlayerMoved, Outcome::ReachedGoal]
+        );
+    }
+
+    #[test]
+    fn moving_and_switching_players_spend_moves_but_bumping_into_walls_doesnt() {
+        let level = test_level(
+            3,
+            2,
+            json!({
+                "players": [
+                    { "x": 0, "y": 0, "color": "Red", "active": true },
+                    { "x": 0, "y": 1, "color": "Blue" },
+                ],
+                "goals": [{ "x": 2, "y": 0, "color": "Red" }, { "x": 2, "y": 1, "color": "Blue" }],
+                "move_limit": 3,
+            }),
+        );
+        let mut state = GameState::new(&level, Rules::default());
+
+        assert_eq!(state.step(Action::Move(Direction::Left)), vec![]);
+        assert_eq!(state.moves_left, Some(3));
+        assert_eq!(
+            state.step(Action::SwitchPlayer),
+            vec![Outcome::PlayerSwitched]
+        );
+        assert_eq!(state.moves_left, Some(2));
+        assert_eq!(
+            state.step(Action::Move(Direction::Right)),
+            vec![Outcome::PlayerMoved]
+        );
+        assert_eq!(
+            state.step(Action::SwitchPlayer),
+            vec![Outcome::PlayerSwitched, Outcome::TimerExpired]
+        );
+        assert_eq!(state.status, Status::Lost);
+    }
+
+    #[test]
+    fn undoing_doesnt_give_moves_back() {
+        let level = test_level(4, 1, json!({ "move_limit": 3 }));
+        let mut state = GameState::new(&level, Rules::default());
+
+        let before = state.clone();
+        state.step(Action::Move(Direction::Right));
+        state.step(Action::Move(Direction::Right));
+        state.rewind(before);
+        assert_eq!(state.players[0].pos, GridPos { x: 0, y: 0 });
+        assert_eq!(state.moves_left, Some(1));
+
+        assert_eq!(
+            state.step(Action::Move(Direction::Right)),
+            vec![Outcome::PlayerMoved, Outcome::TimerExpired]
+        );
+    }
+
+    #[test]
+    fn rewinding_steps_back_through_every_move_until_the_level_ends() {
+        let level = test_level(
+            4,
+            2,
+            json!({ "traps": [{ "x": 3, "y": 0, "color": "Red" }] }),
+        );
+        let mut state = GameState::new(&level, Rules::default());
+
+        let mut history = vec![];
+        for direction in [Direction::Right, Direction::Right, Direction::Up] {
+            history.push(state.clone());
+            state.step(Action::Move(direction));
+        }
+        assert_eq!(state.players[0].pos, GridPos { x: 2, y: 1 });
+
+        for expected in [(2, 0), (1, 0), (0, 0)] {
+            state.rewind(history.pop().unwrap());
+            let (x, y) = expected;
+            assert_eq!(state.players[0].pos, GridPos { x, y });
+        }
+
+        // Once lost, there's no taking it back
+        let before = state.clone();
+        for _ in 0..3 {
+            state.step(Action::Move(Direction::Right));
+        }
+        assert_eq!(state.status, Status::Lost);
+        state.rewind(before);
+        assert_eq!(state.status, Status::Lost);
+        assert_eq!(state.players[0].pos, GridPos { x: 3, y: 0 });
+    }
+
+    #[test]
+    fn walls_block_players_of_any_color_without_hurting() {
+        let level = test_level(
+            2,
+            1,
+            json!({
+                "player": { "x": 0, "y": 0, "color": "Blue" },
+                "walls": [{ "x": 1, "y": 0, "color": "Red" }],
+            }),
+        );
+        let mut state = GameState::new(&level, Rules::default());
+
+        assert_eq!(state.step(Action::Move(Direction::Right)), vec![]);
+        assert_eq!(state.players[0].pos, GridPos { x: 0, y: 0 });
+        assert_eq!(state.status, Status::Playing);
+    }
+
+    #[test]
+    fn paint_tiles_recolor_the_player_and_change_which_traps_hurt() {
+        let level = |paint: &str| {
+            test_level(
+                3,
+                1,
+                json!({
+                    "traps": [{ "x": 2, "y": 0, "color": "Red" }],
+                    "paint_tiles": [{ "x": 1, "y": 0, "color": paint }],
+                }),
+            )
+        };
+        let rules = Rules {
+            traps: TrapRule::SharedChannel,
+            ..Rules::default()
+        };
+
+        let mut state = GameState::new(&level("Blue"), rules);
+        state.step(Action::Move(Direction::Right));
+        assert_eq!(state.players[0].color, GameColor::Blue);
+        assert_eq!(
+            state.step(Action::Move(Direction::Right)),
+            vec![Outcome::PlayerMoved]
+        );
+
+        let mut state = GameState::new(&level("Yellow"), rules);
+        state.step(Action::Move(Direction::Right));
+        assert_eq!(state.players[0].color, GameColor::Yellow);
+        assert_eq!(
+            state.step(Action::Move(Direction::Right)),
+            vec![Outcome::PlayerMoved, Outcome::HitTrap]
+        );
+    }
+}
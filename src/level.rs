@@ -1,48 +1,803 @@
-use bevy::{prelude::*, reflect::TypePath};
-use bevy_common_assets::json::JsonAssetPlugin;
-use ctrl_macros::some_or_return;
-use serde::Deserialize;
-
-use crate::game_mechanics::{
-    GameColor, GameTimer, Goal, GridPos, HitTrapEvent, Player, ReachedGoalEvent, TimerExpiredEvent,
-    Trap,
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypePath,
+    utils::BoxedFuture,
+};
+use ctrl_macros::{some_or_continue, some_or_return};
+use serde::{Deserialize, Deserializer};
+
+use crate::{
+    game_mechanics::{
+        Active, Block, Collected, Controlled, Door, Filled, Game, GameColor, Goal, GridPos,
+        HitTrapEvent, Ice, Inventory, Key, LevelSize, MoveCounter, MoveHistory, ObjectIndex,
+        PaintTile, Player, ReachedGoalEvent, RestartLevelEvent, Switch, Teleporter,
+        TimerExpiredEvent, Trap, Wall,
+    },
+    game_state::{GameState, PatrolPattern, Rules, SwitchKind, WinCondition},
 };
 
 #[derive(Deserialize)]
-struct LevelPlayer {
-    x: i32,
-    y: i32,
-    color: GameColor,
+pub struct LevelPlayer {
+    pub x: i32,
+    pub y: i32,
+    pub color: GameColor,
+    /// Starts in control, the others waiting their turn. Without one, all move at once.
+    #[serde(default)]
+    pub active: bool,
+}
+
+#[derive(Deserialize)]
+pub struct LevelGoal {
+    pub x: i32,
+    pub y: i32,
+    pub color: GameColor,
+}
+
+#[derive(Deserialize)]
+pub struct LevelTrap {
+    pub x: i32,
+    pub y: i32,
+    pub color: GameColor,
+    #[serde(default)]
+    pub patrol: Option<LevelPatrol>,
+    #[serde(default)]
+    pub color_cycle: Option<LevelColorCycle>,
+    /// Switches with the same group turn the trap on and off
+    #[serde(default)]
+    pub group: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct LevelPatrol {
+    /// Cells to walk through after the trap's own position
+    pub path: Vec<LevelPoint>,
+    #[serde(default)]
+    pub pattern: PatrolPattern,
+    /// Seconds between steps, `null` to step along with every player move
+    pub every_seconds: Option<f32>,
+}
+
+#[derive(Deserialize)]
+pub struct LevelColorCycle {
+    pub colors: Vec<GameColor>,
+    /// Index of the color in `colors` the trap starts with
+    #[serde(default)]
+    pub phase: usize,
+    /// Seconds between color changes, `null` to change with every player move
+    pub every_seconds: Option<f32>,
+}
+
+#[derive(Deserialize)]
+pub struct LevelPoint {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Deserialize)]
+pub struct LevelWall {
+    pub x: i32,
+    pub y: i32,
+    pub color: GameColor,
+}
+
+#[derive(Deserialize)]
+pub struct LevelPaintTile {
+    pub x: i32,
+    pub y: i32,
+    pub color: GameColor,
+}
+
+#[derive(Deserialize)]
+pub struct LevelBlock {
+    pub x: i32,
+    pub y: i32,
+    pub color: GameColor,
 }
 
 #[derive(Deserialize)]
-struct LevelGoal {
-    x: i32,
-    y: i32,
-    color: GameColor,
+pub struct LevelKey {
+    pub x: i32,
+    pub y: i32,
+    pub color: GameColor,
 }
 
 #[derive(Deserialize)]
-struct LevelTrap {
-    x: i32,
-    y: i32,
-    color: GameColor,
+pub struct LevelDoor {
+    pub x: i32,
+    pub y: i32,
+    pub color: GameColor,
 }
 
 #[derive(Deserialize)]
-struct Level {
-    player: LevelPlayer,
-    goals: Vec<LevelGoal>,
-    traps: Vec<LevelTrap>,
+pub struct LevelSwitch {
+    pub x: i32,
+    pub y: i32,
+    pub color: GameColor,
+    /// Traps with the same group are switched by it
+    pub group: String,
+    #[serde(default)]
+    pub kind: SwitchKind,
+}
+
+/// Unlike other objects, ice lies under whatever else is on its cell
+#[derive(Deserialize)]
+pub struct LevelIce {
+    pub x: i32,
+    pub y: i32,
+    pub color: GameColor,
+}
+
+#[derive(Deserialize)]
+pub struct LevelTeleporter {
+    pub x: i32,
+    pub y: i32,
+    pub color: GameColor,
+    /// Links the two teleporters of a pair, which share it
+    pub pair: String,
+    /// Only teleport a player of exactly this color
+    #[serde(default)]
+    pub color_locked: bool,
+}
+
+#[derive(Deserialize)]
+pub struct Level {
+    pub width: i32,
+    pub height: i32,
+    /// Seconds to reach the goal, `null` for levels without a timer
+    #[serde(default = "default_time_limit")]
+    pub time_limit: Option<f32>,
+    /// Moves to reach the goal, making the level turn-based: nothing happens in real time,
+    /// so `time_limit` and the intervals of traps are ignored
+    #[serde(default)]
+    pub move_limit: Option<u32>,
+    /// Moves the level can be beaten in, for rating how well it was played
+    #[serde(default)]
+    pub par: Option<u32>,
+    #[serde(default)]
+    pub win_condition: WinCondition,
+    /// All moved by the same input, unless one is `active`. A single `player` is accepted too.
+    /// With several, each only counts on goals of exactly their color.
+    #[serde(alias = "player", deserialize_with = "one_or_many")]
+    pub players: Vec<LevelPlayer>,
+    pub goals: Vec<LevelGoal>,
+    pub traps: Vec<LevelTrap>,
+    #[serde(default)]
+    pub walls: Vec<LevelWall>,
+    #[serde(default)]
+    pub paint_tiles: Vec<LevelPaintTile>,
+    #[serde(default)]
+    pub teleporters: Vec<LevelTeleporter>,
+    #[serde(default)]
+    pub ice: Vec<LevelIce>,
+    #[serde(default)]
+    pub blocks: Vec<LevelBlock>,
+    #[serde(default)]
+    pub keys: Vec<LevelKey>,
+    #[serde(default)]
+    pub doors: Vec<LevelDoor>,
+    #[serde(default)]
+    pub switches: Vec<LevelSwitch>,
+}
+
+fn default_time_limit() -> Option<f32> {
+    Some(25.0)
+}
+
+/// Whether a number of seconds can be waited for, being finite and at least a nanosecond long
+fn is_duration(seconds: f32) -> bool {
+    Duration::try_from_secs_f32(seconds).is_ok_and(|duration| !duration.is_zero())
+}
+
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<LevelPlayer>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(LevelPlayer),
+        Many(Vec<LevelPlayer>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(player) => vec![player],
+        OneOrMany::Many(players) => players,
+    })
 }
 
 #[derive(Resource)]
-struct LevelsHandle(Handle<Levels>);
+pub struct LevelsHandle(Handle<Levels>);
 
 #[derive(Deserialize, bevy::reflect::TypeUuid, Resource, TypePath)]
 #[uuid = "413be529-bfeb-41b3-9db0-4b8b380a2c46"] // <-- keep me unique
-struct Levels {
-    levels: Vec<Level>,
+pub struct Levels {
+    #[serde(default)]
+    pub rules: Rules,
+    pub levels: Vec<Level>,
+}
+
+impl Levels {
+    /// Lists every problem in the pack, so they can all be fixed in one go
+    fn problems(&self) -> Vec<String> {
+        self.levels
+            .iter()
+            .enumerate()
+            .flat_map(|(index, level)| {
+                level
+                    .problems()
+                    .into_iter()
+                    .map(move |problem| format!("level {index}: {problem}"))
+            })
+            .collect()
+    }
+}
+
+impl Level {
+    fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+
+        if self.width <= 0 || self.height <= 0 {
+            problems.push(format!("invalid size {}x{}", self.width, self.height));
+        }
+        if self.move_limit == Some(0) {
+            problems.push("move limit is 0".to_owned());
+        }
+        if self.par == Some(0) {
+            problems.push("par is 0".to_owned());
+        }
+        if let Some(seconds) = self.time_limit {
+            if !is_duration(seconds) {
+                problems.push(format!("time limit {seconds} is not a usable duration"));
+            }
+        }
+        if self.goals.is_empty() {
+            problems.push("no goals".to_owned());
+        }
+        if self.players.is_empty() {
+            problems.push("no players".to_owned());
+        }
+        let active: Vec<String> = self
+            .players
+            .iter()
+            .filter(|player| player.active)
+            .map(|player| format!("({}, {})", player.x, player.y))
+            .collect();
+        if active.len() > 1 {
+            problems.push(format!(
+                "players at {} are all active, only one can start in control",
+                active.join(", ")
+            ));
+        }
+        if self.players.len() > 1
+            && self.win_condition == WinCondition::AnyGoal
+            && self.goals.len() < self.players.len()
+        {
+            problems.push(format!(
+                "{} goals aren't enough for {} players to stand on at once",
+                self.goals.len(),
+                self.players.len()
+            ));
+        }
+        if self.players.len() > 1 {
+            // Each player needs a goal of their color, which paint tiles can change on the way
+            let reachable = |color: GameColor| {
+                self.goals.iter().any(|goal| goal.color == color)
+                    || self
+                        .paint_tiles
+                        .iter()
+                        .any(|tile| self.goals.iter().any(|goal| goal.color == tile.color))
+            };
+            for player in &self.players {
+                if !reachable(player.color) {
+                    problems.push(format!(
+                        "{:?} player at ({}, {}) has no goal of their color",
+                        player.color, player.x, player.y
+                    ));
+                }
+            }
+        }
+
+        let objects: Vec<(&str, i32, i32)> = self
+            .players
+            .iter()
+            .map(|player| ("player", player.x, player.y))
+            .chain(self.goals.iter().map(|goal| ("goal", goal.x, goal.y)))
+            .chain(self.traps.iter().map(|trap| ("trap", trap.x, trap.y)))
+            .chain(self.walls.iter().map(|wall| ("wall", wall.x, wall.y)))
+            .chain(
+                self.paint_tiles
+                    .iter()
+                    .map(|tile| ("paint tile", tile.x, tile.y)),
+            )
+            .chain(
+                self.teleporters
+                    .iter()
+                    .map(|teleporter| ("teleporter", teleporter.x, teleporter.y)),
+            )
+            .chain(self.blocks.iter().map(|block| ("block", block.x, block.y)))
+            .chain(self.keys.iter().map(|key| ("key", key.x, key.y)))
+            .chain(self.doors.iter().map(|door| ("door", door.x, door.y)))
+            .chain(
+                self.switches
+                    .iter()
+                    .map(|switch| ("switch", switch.x, switch.y)),
+            )
+            .collect();
+
+        for ice in &self.ice {
+            if ice.x < 0 || ice.x >= self.width || ice.y < 0 || ice.y >= self.height {
+                problems.push(format!(
+                    "ice at ({}, {}) is outside the {}x{} grid",
+                    ice.x, ice.y, self.width, self.height
+                ));
+            }
+        }
+
+        for switch in &self.switches {
+            if !self
+                .traps
+                .iter()
+                .any(|trap| trap.group.as_ref() == Some(&switch.group))
+            {
+                problems.push(format!(
+                    "switch at ({}, {}) has no traps in group \"{}\"",
+                    switch.x, switch.y, switch.group
+                ));
+            }
+        }
+        for trap in &self.traps {
+            let group = some_or_continue!(&trap.group);
+            if !self.switches.iter().any(|switch| &switch.group == group) {
+                problems.push(format!(
+                    "trap at ({}, {}) has no switches in group \"{group}\"",
+                    trap.x, trap.y
+                ));
+            }
+        }
+
+        for (i, teleporter) in self.teleporters.iter().enumerate() {
+            // Report each pair once, at its first teleporter
+            if self.teleporters[..i]
+                .iter()
+                .any(|other| other.pair == teleporter.pair)
+            {
+                continue;
+            }
+            let count = self
+                .teleporters
+                .iter()
+                .filter(|other| other.pair == teleporter.pair)
+                .count();
+            if count != 2 {
+                problems.push(format!(
+                    "teleporter pair \"{}\" at ({}, {}) has {count} teleporter(s) instead of 2",
+                    teleporter.pair, teleporter.x, teleporter.y
+                ));
+            }
+        }
+
+        for trap in &self.traps {
+            let patrol = some_or_continue!(&trap.patrol);
+            for point in &patrol.path {
+                if point.x < 0 || point.x >= self.width || point.y < 0 || point.y >= self.height {
+                    problems.push(format!(
+                        "patrol of trap at ({}, {}) goes to ({}, {}), outside the {}x{} grid",
+                        trap.x, trap.y, point.x, point.y, self.width, self.height
+                    ));
+                }
+            }
+            if let Some(seconds) = patrol
+                .every_seconds
+                .filter(|&seconds| !is_duration(seconds))
+            {
+                problems.push(format!(
+                    "patrol of trap at ({}, {}) has interval {seconds}, not a usable duration",
+                    trap.x, trap.y
+                ));
+            }
+        }
+
+        for trap in &self.traps {
+            let cycle = some_or_continue!(&trap.color_cycle);
+            if cycle.colors.is_empty() {
+                problems.push(format!(
+                    "color cycle of trap at ({}, {}) has no colors",
+                    trap.x, trap.y
+                ));
+            }
+            if let Some(seconds) = cycle.every_seconds.filter(|&seconds| !is_duration(seconds)) {
+                problems.push(format!(
+                    "color cycle of trap at ({}, {}) has interval {seconds}, not a usable duration",
+                    trap.x, trap.y
+                ));
+            }
+        }
+
+        for &(name, x, y) in &objects {
+            if x < 0 || x >= self.width || y < 0 || y >= self.height {
+                problems.push(format!(
+                    "{name} at ({x}, {y}) is outside the {}x{} grid",
+                    self.width, self.height
+                ));
+            }
+        }
+
+        for (i, &(name, x, y)) in objects.iter().enumerate() {
+            for &(other_name, other_x, other_y) in &objects[i + 1..] {
+                if (x, y) == (other_x, other_y) {
+                    problems.push(format!("{name} and {other_name} overlap at ({x}, {y})"));
+                }
+            }
+        }
+
+        problems
+    }
+}
+
+/// Loads a pack of levels either from our own JSON format or from an LDtk project
+#[derive(Default)]
+struct LevelsLoader;
+
+impl AssetLoader for LevelsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let is_ldtk = load_context.path().extension() == Some("ldtk".as_ref());
+            let levels = parse_levels(bytes, is_ldtk)?;
+            load_context.set_default_asset(LoadedAsset::new(levels));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.json", "ldtk"]
+    }
+}
+
+/// Parses and validates a level pack, refusing it if any level has problems
+pub fn parse_levels(bytes: &[u8], is_ldtk: bool) -> anyhow::Result<Levels> {
+    let levels = if is_ldtk {
+        let project: LdtkProject = serde_json::from_slice(bytes)?;
+        levels_from_ldtk(project)?
+    } else {
+        serde_json::from_slice(bytes)?
+    };
+
+    let problems = levels.problems();
+    if !problems.is_empty() {
+        bail!(
+            "{} problem(s) in level pack:\n{}",
+            problems.len(),
+            problems.join("\n")
+        );
+    }
+
+    Ok(levels)
+}
+
+// LDtk project format, only the parts we read
+
+#[derive(Deserialize)]
+struct LdtkProject {
+    levels: Vec<LdtkLevel>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LdtkLevel {
+    identifier: String,
+    px_wid: i32,
+    px_hei: i32,
+    field_instances: Vec<LdtkField>,
+    layer_instances: Option<Vec<LdtkLayer>>,
+}
+
+#[derive(Deserialize)]
+struct LdtkField {
+    #[serde(rename = "__identifier")]
+    identifier: String,
+    #[serde(rename = "__value")]
+    value: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LdtkLayer {
+    #[serde(rename = "__gridSize")]
+    grid_size: i32,
+    entity_instances: Vec<LdtkEntity>,
+}
+
+#[derive(Deserialize)]
+struct LdtkEntity {
+    #[serde(rename = "__identifier")]
+    identifier: String,
+    #[serde(rename = "__grid")]
+    grid: [i32; 2],
+    #[serde(rename = "fieldInstances", default)]
+    field_instances: Vec<LdtkField>,
+}
+
+#[derive(Deserialize)]
+struct LdtkPoint {
+    cx: i32,
+    cy: i32,
+}
+
+fn levels_from_ldtk(mut project: LdtkProject) -> anyhow::Result<Levels> {
+    // Levels are played in the order of their identifiers (Level_0, Level_1, ..., Level_10)
+    project
+        .levels
+        .sort_by_cached_key(|ldtk_level| level_order(&ldtk_level.identifier));
+
+    // LDtk has no project-wide fields, so the rules are level fields that have to agree
+    let rules = match project.levels.first() {
+        Some(ldtk_level) => rules_from_ldtk(ldtk_level)?,
+        None => Rules::default(),
+    };
+    for ldtk_level in &project.levels {
+        if rules_from_ldtk(ldtk_level)? != rules {
+            bail!(
+                "{}: rules differ from the rest of the levels",
+                ldtk_level.identifier
+            );
+        }
+    }
+
+    let levels = project
+        .levels
+        .iter()
+        .map(level_from_ldtk)
+        .collect::<anyhow::Result<_>>()?;
+
+    Ok(Levels { rules, levels })
+}
+
+/// Sorts identifiers by their text, then by the number they end with
+fn level_order(identifier: &str) -> (String, Option<u64>) {
+    let name = identifier.trim_end_matches(|c: char| c.is_ascii_digit());
+    (name.to_owned(), identifier[name.len()..].parse().ok())
+}
+
+fn rules_from_ldtk(ldtk_level: &LdtkLevel) -> anyhow::Result<Rules> {
+    let mut rules = Rules::default();
+
+    if let Some(value) = ldtk_field(&ldtk_level.field_instances, "TrapRule") {
+        rules.traps = serde_json::from_value(value.clone())
+            .map_err(|err| anyhow!("{}: TrapRule: {err}", ldtk_level.identifier))?;
+    }
+    if let Some(value) = ldtk_field(&ldtk_level.field_instances, "GoalRule") {
+        rules.goals = serde_json::from_value(value.clone())
+            .map_err(|err| anyhow!("{}: GoalRule: {err}", ldtk_level.identifier))?;
+    }
+
+    Ok(rules)
+}
+
+fn level_from_ldtk(ldtk_level: &LdtkLevel) -> anyhow::Result<Level> {
+    let layers = ldtk_level.layer_instances.as_ref().ok_or_else(|| {
+        anyhow!(
+            "{}: external level files are not supported",
+            ldtk_level.identifier
+        )
+    })?;
+    let grid_size = layers
+        .first()
+        .ok_or_else(|| anyhow!("{}: no layers", ldtk_level.identifier))?
+        .grid_size;
+
+    let mut players = vec![];
+    let mut goals = vec![];
+    let mut traps = vec![];
+    let mut walls = vec![];
+    let mut paint_tiles = vec![];
+    let mut teleporters = vec![];
+    let mut ice = vec![];
+    let mut blocks = vec![];
+    let mut keys = vec![];
+    let mut doors = vec![];
+    let mut switches = vec![];
+
+    for entity in layers.iter().flat_map(|layer| &layer.entity_instances) {
+        let (color, kind) = split_entity_identifier(&entity.identifier).ok_or_else(|| {
+            anyhow!(
+                "{}: unknown entity {}",
+                ldtk_level.identifier,
+                entity.identifier
+            )
+        })?;
+        let [x, y] = entity.grid;
+
+        match kind {
+            "Player" => players.push(LevelPlayer {
+                x,
+                y,
+                color,
+                active: match ldtk_field(&entity.field_instances, "Active") {
+                    Some(value) => serde_json::from_value(value.clone())
+                        .map_err(|err| anyhow!("{}: Active: {err}", ldtk_level.identifier))?,
+                    None => false,
+                },
+            }),
+            "Goal" => {
+                let order = ldtk_field(&entity.field_instances, "Order").and_then(|v| v.as_i64());
+                goals.push((order, LevelGoal { x, y, color }));
+            }
+            "Trap" => traps.push(LevelTrap {
+                x,
+                y,
+                color,
+                patrol: patrol_from_ldtk(entity)
+                    .map_err(|err| anyhow!("{}: {err}", ldtk_level.identifier))?,
+                color_cycle: color_cycle_from_ldtk(entity)
+                    .map_err(|err| anyhow!("{}: {err}", ldtk_level.identifier))?,
+                group: ldtk_field(&entity.field_instances, "Group")
+                    .and_then(|value| value.as_str())
+                    .map(str::to_owned),
+            }),
+            "Wall" => walls.push(LevelWall { x, y, color }),
+            "PaintTile" => paint_tiles.push(LevelPaintTile { x, y, color }),
+            "Ice" => ice.push(LevelIce { x, y, color }),
+            "Block" => blocks.push(LevelBlock { x, y, color }),
+            "Key" => keys.push(LevelKey { x, y, color }),
+            "Door" => doors.push(LevelDoor { x, y, color }),
+            "Switch" => switches.push(LevelSwitch {
+                x,
+                y,
+                color,
+                group: ldtk_field(&entity.field_instances, "Group")
+                    .and_then(|value| value.as_str())
+                    .unwrap_or_default()
+                    .to_owned(),
+                kind: match ldtk_field(&entity.field_instances, "Kind") {
+                    Some(value) => serde_json::from_value(value.clone())
+                        .map_err(|err| anyhow!("{}: Kind: {err}", ldtk_level.identifier))?,
+                    None => SwitchKind::default(),
+                },
+            }),
+            "Teleporter" => teleporters.push(LevelTeleporter {
+                x,
+                y,
+                color,
+                pair: ldtk_field(&entity.field_instances, "Pair")
+                    .and_then(|value| value.as_str())
+                    .unwrap_or_default()
+                    .to_owned(),
+                color_locked: ldtk_field(&entity.field_instances, "ColorLocked")
+                    .and_then(|value| value.as_bool())
+                    .unwrap_or(false),
+            }),
+            _ => bail!(
+                "{}: unknown entity {}",
+                ldtk_level.identifier,
+                entity.identifier
+            ),
+        }
+    }
+
+    // Goals without an order come after the ordered ones
+    goals.sort_by_key(|&(order, _)| order.unwrap_or(i64::MAX));
+    let goals = goals.into_iter().map(|(_, goal)| goal).collect();
+
+    let win_condition = match ldtk_field(&ldtk_level.field_instances, "WinCondition") {
+        Some(value) => serde_json::from_value(value.clone())
+            .map_err(|err| anyhow!("{}: WinCondition: {err}", ldtk_level.identifier))?,
+        None => WinCondition::default(),
+    };
+
+    let move_limit = ldtk_field(&ldtk_level.field_instances, "MoveLimit")
+        .and_then(|value| value.as_u64())
+        .map(|moves| moves as u32);
+
+    let par = ldtk_field(&ldtk_level.field_instances, "Par")
+        .and_then(|value| value.as_u64())
+        .map(|moves| moves as u32);
+
+    let time_limit = match ldtk_field(&ldtk_level.field_instances, "TimeLimit") {
+        Some(value) => value.as_f64().map(|seconds| seconds as f32),
+        None => default_time_limit(),
+    };
+
+    Ok(Level {
+        width: ldtk_level.px_wid / grid_size,
+        height: ldtk_level.px_hei / grid_size,
+        time_limit,
+        move_limit,
+        par,
+        win_condition,
+        players,
+        goals,
+        traps,
+        walls,
+        paint_tiles,
+        teleporters,
+        ice,
+        blocks,
+        keys,
+        doors,
+        switches,
+    })
+}
+
+fn patrol_from_ldtk(entity: &LdtkEntity) -> anyhow::Result<Option<LevelPatrol>> {
+    let path: Vec<LdtkPoint> = match ldtk_field(&entity.field_instances, "Path") {
+        Some(value) => serde_json::from_value(value.clone())?,
+        None => vec![],
+    };
+    if path.is_empty() {
+        return Ok(None);
+    }
+
+    let pattern = match ldtk_field(&entity.field_instances, "Pattern") {
+        Some(value) => serde_json::from_value(value.clone())?,
+        None => PatrolPattern::default(),
+    };
+    let every_seconds = ldtk_field(&entity.field_instances, "EverySeconds")
+        .and_then(|value| value.as_f64())
+        .map(|seconds| seconds as f32);
+
+    Ok(Some(LevelPatrol {
+        path: path
+            .into_iter()
+            .map(|point| LevelPoint {
+                x: point.cx,
+                y: point.cy,
+            })
+            .collect(),
+        pattern,
+        every_seconds,
+    }))
+}
+
+fn color_cycle_from_ldtk(entity: &LdtkEntity) -> anyhow::Result<Option<LevelColorCycle>> {
+    let colors: Vec<GameColor> = match ldtk_field(&entity.field_instances, "ColorCycle") {
+        Some(value) => serde_json::from_value(value.clone())?,
+        None => vec![],
+    };
+    if colors.is_empty() {
+        return Ok(None);
+    }
+
+    let phase = ldtk_field(&entity.field_instances, "CyclePhase")
+        .and_then(|value| value.as_u64())
+        .unwrap_or(0) as usize;
+    let every_seconds = ldtk_field(&entity.field_instances, "CycleEverySeconds")
+        .and_then(|value| value.as_f64())
+        .map(|seconds| seconds as f32);
+
+    Ok(Some(LevelColorCycle {
+        colors,
+        phase,
+        every_seconds,
+    }))
+}
+
+fn ldtk_field<'a>(fields: &'a [LdtkField], identifier: &str) -> Option<&'a serde_json::Value> {
+    fields
+        .iter()
+        .find(|field| field.identifier == identifier)
+        .map(|field| &field.value)
+}
+
+/// Splits an LDtk entity identifier such as `CyanTrap` into its color and kind
+fn split_entity_identifier(identifier: &str) -> Option<(GameColor, &str)> {
+    const COLORS: [(&str, GameColor); 7] = [
+        ("Red", GameColor::Red),
+        ("Green", GameColor::Green),
+        ("Blue", GameColor::Blue),
+        ("Yellow", GameColor::Yellow),
+        ("Cyan", GameColor::Cyan),
+        ("Pink", GameColor::Pink),
+        ("White", GameColor::White),
+    ];
+
+    COLORS
+        .iter()
+        .find_map(|&(name, color)| identifier.strip_prefix(name).map(|kind| (color, kind)))
 }
 
 #[derive(Resource)]
@@ -50,29 +805,35 @@ pub struct LevelInfo {
     pub desired_index: Option<i32>,
     pub index: Option<i32>,
     pub total_levels: usize,
+    /// Moves the current level can be beaten in
+    pub par: Option<u32>,
 }
 
 pub struct LevelPlugin;
 
 impl Plugin for LevelPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_plugins(JsonAssetPlugin::<Levels>::new(&["level.json"]))
+        app.add_asset::<Levels>()
+            .add_asset_loader(LevelsLoader)
             .add_systems(Startup, load_first_level)
             .add_systems(Startup, setup)
             .add_systems(Update, reload_level_on_death)
             .add_systems(Update, reload_level_on_timer_expired)
+            .add_systems(Update, reload_level_on_restart)
+            .add_systems(Update, reload_level_on_levels_modified)
             .add_systems(Update, load_level_on_level_change)
             .add_systems(Update, go_to_next_level_on_goal)
             .insert_resource(LevelInfo {
                 index: None,
                 desired_index: None,
                 total_levels: 0,
+                par: None,
             });
     }
 }
 
 fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let handle: Handle<Levels> = asset_server.load("levels.level.json");
+    let handle: Handle<Levels> = asset_server.load("levels/bevy-jam-2-levels.ldtk");
     commands.insert_resource(LevelsHandle(handle));
 }
 
@@ -100,6 +861,28 @@ fn reload_level_on_timer_expired(
     }
 }
 
+fn reload_level_on_restart(
+    mut ev_restart: EventReader<RestartLevelEvent>,
+    mut level_info: ResMut<LevelInfo>,
+) {
+    for _ in ev_restart.iter() {
+        level_info.desired_index = level_info.index;
+        level_info.index = None;
+    }
+}
+
+fn reload_level_on_levels_modified(
+    mut ev_asset: EventReader<AssetEvent<Levels>>,
+    mut level_info: ResMut<LevelInfo>,
+) {
+    for ev in ev_asset.iter() {
+        if let AssetEvent::Modified { .. } = ev {
+            level_info.desired_index = level_info.index;
+            level_info.index = None;
+        }
+    }
+}
+
 fn go_to_next_level_on_goal(
     mut ev: EventReader<ReachedGoalEvent>,
     mut level_info: ResMut<LevelInfo>,
@@ -109,7 +892,7 @@ fn go_to_next_level_on_goal(
     }
 }
 
-fn load_level_on_level_change(
+pub fn load_level_on_level_change(
     mut commands: Commands,
     handle: Res<LevelsHandle>,
     levels: Res<Assets<Levels>>,
@@ -118,7 +901,10 @@ fn load_level_on_level_change(
 
     q_existing_objects: Query<Entity, With<GridPos>>,
 
-    timer: ResMut<GameTimer>,
+    mut game: ResMut<Game>,
+    mut history: ResMut<MoveHistory>,
+    mut move_counter: ResMut<MoveCounter>,
+    level_size: ResMut<LevelSize>,
 ) {
     if level_info.index == level_info.desired_index || level_info.desired_index.is_none() {
         return;
@@ -129,57 +915,348 @@ fn load_level_on_level_change(
     for entity in q_existing_objects.iter() {
         commands.entity(entity).despawn();
     }
+    game.0 = None;
+    history.0.clear();
+    move_counter.0 = 0;
+    level_info.par = None;
 
     level_info.total_levels = levels.levels.len();
 
     level_info.index = level_info.desired_index;
-    let index = level_info.index.unwrap_or(0) as usize;
+    let index = level_info.index.unwrap_or(0);
 
-    if index >= level_info.total_levels {
+    // Past the last level means the game has been won
+    if index == level_info.total_levels as i32 {
         return;
     }
 
-    let level = &levels.levels[index];
+    let level = match usize::try_from(index)
+        .ok()
+        .and_then(|index| levels.levels.get(index))
+    {
+        Some(level) => level,
+        None => {
+            error!(
+                "Level {index} doesn't exist, there are {} levels",
+                level_info.total_levels
+            );
+            return;
+        }
+    };
 
-    spawn_level(commands, level, timer);
+    level_info.par = level.par;
+    spawn_level(commands, level, levels.rules, game, level_size);
 }
 
-fn spawn_level(mut commands: Commands, level: &Level, mut timer: ResMut<GameTimer>) {
-    let player = &level.player;
+fn spawn_level(
+    mut commands: Commands,
+    level: &Level,
+    rules: Rules,
+    mut game: ResMut<Game>,
+    mut level_size: ResMut<LevelSize>,
+) {
+    let state = GameState::new(level, rules);
+    level_size.set_if_neq(state.size);
 
-    commands
-        .spawn_empty()
-        .insert(Player)
-        .insert(GridPos {
-            x: player.x,
-            y: player.y,
-        })
-        .insert(player.color)
-        .insert(Name::new("Player"));
+    for (index, player) in state.players.iter().enumerate() {
+        commands
+            .spawn_empty()
+            .insert(Player)
+            .insert(ObjectIndex(index))
+            .insert(Inventory::default())
+            .insert(Controlled(state.active_player == Some(index)))
+            .insert(player.pos)
+            .insert(player.color)
+            .insert(Name::new("Player"));
+    }
 
-    for goal in &level.goals {
+    for (index, goal) in state.goals.iter().enumerate() {
         commands
             .spawn_empty()
             .insert(Goal)
-            .insert(GridPos {
-                x: goal.x,
-                y: goal.y,
-            })
+            .insert(ObjectIndex(index))
+            .insert(Collected(false))
+            .insert(goal.pos)
             .insert(goal.color)
             .insert(Name::new("Goal"));
     }
 
-    for trap in &level.traps {
+    for (index, trap) in state.traps.iter().enumerate() {
         commands
             .spawn_empty()
             .insert(Trap)
-            .insert(GridPos {
-                x: trap.x,
-                y: trap.y,
-            })
+            .insert(ObjectIndex(index))
+            .insert(Filled(false))
+            .insert(Active(trap.active))
+            .insert(trap.pos)
             .insert(trap.color)
             .insert(Name::new("Trap"));
     }
 
-    timer.0 = Some(Timer::from_seconds(25.0, TimerMode::Once));
+    for wall in &state.walls {
+        commands
+            .spawn_empty()
+            .insert(Wall)
+            .insert(wall.pos)
+            .insert(wall.color)
+            .insert(Name::new("Wall"));
+    }
+
+    for tile in &state.paint_tiles {
+        commands
+            .spawn_empty()
+            .insert(PaintTile)
+            .insert(tile.pos)
+            .insert(tile.color)
+            .insert(Name::new("Paint Tile"));
+    }
+
+    for teleporter in &state.teleporters {
+        commands
+            .spawn_empty()
+            .insert(Teleporter)
+            .insert(teleporter.pos)
+            .insert(teleporter.color)
+            .insert(Name::new("Teleporter"));
+    }
+
+    for ice in &state.ice {
+        commands
+            .spawn_empty()
+            .insert(Ice)
+            .insert(ice.pos)
+            .insert(ice.color)
+            .insert(Name::new("Ice"));
+    }
+
+    for (index, block) in state.blocks.iter().enumerate() {
+        commands
+            .spawn_empty()
+            .insert(Block)
+            .insert(ObjectIndex(index))
+            .insert(Filled(false))
+            .insert(block.pos)
+            .insert(block.color)
+            .insert(Name::new("Block"));
+    }
+
+    for (index, key) in state.keys.iter().enumerate() {
+        commands
+            .spawn_empty()
+            .insert(Key)
+            .insert(ObjectIndex(index))
+            .insert(Collected(false))
+            .insert(key.pos)
+            .insert(key.color)
+            .insert(Name::new("Key"));
+    }
+
+    for door in &state.doors {
+        commands
+            .spawn_empty()
+            .insert(Door)
+            .insert(door.pos)
+            .insert(door.color)
+            .insert(Name::new("Door"));
+    }
+
+    for switch in &state.switches {
+        commands
+            .spawn_empty()
+            .insert(Switch)
+            .insert(switch.pos)
+            .insert(switch.color)
+            .insert(Name::new("Switch"));
+    }
+
+    game.0 = Some(state);
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_support::test_level;
+
+    /// An LDtk level with a player and a goal, `width` cells wide
+    fn ldtk_level(identifier: &str, width: i32) -> serde_json::Value {
+        json!({
+            "identifier": identifier,
+            "pxWid": width * 16,
+            "pxHei": 16,
+            "fieldInstances": [],
+            "layerInstances": [{
+                "__gridSize": 16,
+                "entityInstances": [
+                    { "__identifier": "WhitePlayer", "__grid": [0, 0] },
+                    { "__identifier": "WhiteGoal", "__grid": [1, 0] },
+                ],
+            }],
+        })
+    }
+
+    #[test]
+    fn ldtk_levels_are_played_in_numeric_order() {
+        let project = json!({
+            "levels": [
+                ldtk_level("Level_10", 5),
+                ldtk_level("Level_2", 4),
+                ldtk_level("Level_0", 2),
+                ldtk_level("Level_1", 3),
+            ],
+        });
+
+        let levels = parse_levels(project.to_string().as_bytes(), true).unwrap();
+
+        let widths: Vec<i32> = levels.levels.iter().map(|level| level.width).collect();
+        assert_eq!(widths, vec![2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn every_problem_is_reported_with_its_level_and_position() {
+        let pack = json!({
+            "levels": [
+                {
+                    "width": 3,
+                    "height": 2,
+                    "player": { "x": 0, "y": 0, "color": "White" },
+                    "goals": [{ "x": 5, "y": 1, "color": "White" }],
+                    "traps": [{ "x": 0, "y": 0, "color": "Red" }],
+                },
+                {
+                    "width": 3,
+                    "height": 2,
+                    "player": { "x": 0, "y": 0, "color": "White" },
+                    "goals": [],
+                    "traps": [],
+                    "teleporters": [{ "x": 1, "y": 1, "color": "Red", "pair": "a" }],
+                },
+            ],
+        });
+
+        let err = parse_levels(pack.to_string().as_bytes(), false)
+            .err()
+            .unwrap()
+            .to_string();
+
+        assert!(err.starts_with("4 problem(s)"), "{err}");
+        for problem in [
+            "level 0: goal at (5, 1) is outside the 3x2 grid",
+            "level 0: player and trap overlap at (0, 0)",
+            "level 1: no goals",
+            "level 1: teleporter pair \"a\" at (1, 1) has 1 teleporter(s) instead of 2",
+        ] {
+            assert!(
+                err.lines().any(|line| line == problem),
+                "{problem} missing in {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn intervals_and_time_limits_have_to_be_usable_durations() {
+        let mut level = test_level(
+            3,
+            1,
+            json!({
+                "goals": [{ "x": 2, "y": 0, "color": "White" }],
+                "traps": [{
+                    "x": 1, "y": 0, "color": "Red",
+                    "patrol": { "path": [], "every_seconds": 1e-12 },
+                    "color_cycle": { "colors": ["Red"], "every_seconds": 1e30 },
+                }],
+            }),
+        );
+        level.time_limit = Some(f32::NAN);
+
+        assert_eq!(
+            level.problems(),
+            vec![
+                "time limit NaN is not a usable duration",
+                "patrol of trap at (1, 0) has interval 0.000000000001, not a usable duration",
+                "color cycle of trap at (1, 0) has interval 1000000000000000000000000000000, not a usable duration",
+            ]
+        );
+
+        level.time_limit = Some(0.5);
+        level.traps[0].patrol.as_mut().unwrap().every_seconds = Some(0.001);
+        level.traps[0].color_cycle.as_mut().unwrap().every_seconds = None;
+        assert_eq!(level.problems(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn the_active_player_is_flagged_on_its_ldtk_entity() {
+        let level = |actives: [serde_json::Value; 2]| {
+            json!({
+                "levels": [{
+                    "identifier": "Level_0",
+                    "pxWid": 48,
+                    "pxHei": 32,
+                    "fieldInstances": [],
+                    "layerInstances": [{
+                        "__gridSize": 16,
+                        "entityInstances": [
+                            {
+                                "__identifier": "RedPlayer",
+                                "__grid": [0, 0],
+                                "fieldInstances": [{ "__identifier": "Active", "__value": actives[0] }],
+                            },
+                            {
+                                "__identifier": "BluePlayer",
+                                "__grid": [0, 1],
+                                "fieldInstances": [{ "__identifier": "Active", "__value": actives[1] }],
+                            },
+                            { "__identifier": "RedGoal", "__grid": [2, 0] },
+                            { "__identifier": "BlueGoal", "__grid": [2, 1] },
+                        ],
+                    }],
+                }],
+            })
+            .to_string()
+        };
+
+        let levels = parse_levels(level([json!(false), json!(true)]).as_bytes(), true).unwrap();
+        let state = GameState::new(&levels.levels[0], levels.rules);
+        assert_eq!(state.active_player, Some(1));
+
+        let err = parse_levels(level([json!(true), json!(true)]).as_bytes(), true)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(
+            err.contains("level 0: players at (0, 0), (0, 1) are all active"),
+            "{err}"
+        );
+
+        let err = parse_levels(level([json!(false), json!(1)]).as_bytes(), true)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.starts_with("Level_0: Active: "), "{err}");
+    }
+
+    #[test]
+    fn every_player_needs_a_goal_of_their_color() {
+        let mut level = test_level(
+            3,
+            2,
+            json!({
+                "players": [
+                    { "x": 0, "y": 0, "color": "Red" },
+                    { "x": 0, "y": 1, "color": "Blue" },
+                ],
+                "goals": [{ "x": 2, "y": 0, "color": "Red" }, { "x": 2, "y": 1, "color": "Red" }],
+            }),
+        );
+        assert_eq!(
+            level.problems(),
+            vec!["Blue player at (0, 1) has no goal of their color"]
+        );
+
+        // Unless a paint tile can give them one
+        level.paint_tiles =
+            serde_json::from_value(json!([{ "x": 1, "y": 1, "color": "Red" }])).unwrap();
+        assert_eq!(level.problems(), Vec::<String>::new());
+    }
 }
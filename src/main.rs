@@ -8,16 +8,27 @@ use bevy::{prelude::*, window::WindowMode, asset::ChangeWatcher};
 // use bevy_inspector_egui::quick::WorldInspectorPlugin;
 
 use camera_rendering::CameraRendering;
+use controls::ControlsPlugin;
 use game_mechanics::GameMechanicsPlugin;
 use level::LevelPlugin;
 use object_rendering::ObjectRenderingPlugin;
+use pointer::PointerPlugin;
+use progress::ProgressPlugin;
 use text_display::TextDisplayPlugin;
 
 mod audio;
 mod camera_rendering;
+mod controls;
 mod game_mechanics;
+mod game_state;
 mod level;
 mod object_rendering;
+mod pointer;
+mod progress;
+#[cfg(test)]
+mod solver;
+#[cfg(test)]
+mod test_support;
 mod text_display;
 
 fn main() {
@@ -46,9 +57,12 @@ fn main() {
 
     app.add_plugins(AudioPlugin)
         .add_plugins(CameraRendering)
+        .add_plugins(ControlsPlugin)
         .add_plugins(GameMechanicsPlugin)
         .add_plugins(LevelPlugin)
         .add_plugins(ObjectRenderingPlugin)
+        .add_plugins(PointerPlugin)
+        .add_plugins(ProgressPlugin)
         .add_plugins(TextDisplayPlugin)
         .run();
 }
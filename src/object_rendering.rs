@@ -1,6 +1,10 @@
 use bevy::{prelude::*, render::view::RenderLayers};
+use ctrl_macros::some_or_continue;
 
-use crate::game_mechanics::{GameColor, Goal, GridPos, Player, Trap, GRID_SIZE_Y};
+use crate::game_mechanics::{
+    Active, Block, Collected, Controlled, Door, Filled, GameColor, Goal, GridPos, Ice, Key,
+    LevelSize, PaintTile, Player, Switch, TeleportedEvent, Teleporter, Trap, Wall,
+};
 
 pub struct ObjectRenderingPlugin;
 
@@ -9,14 +13,32 @@ impl Plugin for ObjectRenderingPlugin {
         app.add_systems(Update, spawn_player_object)
             .add_systems(Update, spawn_trap_object)
             .add_systems(Update, spawn_goal_object)
+            .add_systems(Update, spawn_wall_object)
+            .add_systems(Update, spawn_paint_tile_object)
+            .add_systems(Update, spawn_teleporter_object)
+            .add_systems(Update, spawn_ice_object)
+            .add_systems(Update, spawn_block_object)
+            .add_systems(Update, spawn_key_object)
+            .add_systems(Update, spawn_door_object)
+            .add_systems(Update, spawn_switch_object)
+            .add_systems(Update, pulse_teleporters_on_teleport)
+            .add_systems(Update, animate_pulse)
             .add_systems(Update, update_material_color)
             .add_systems(Update, update_visibility)
+            .add_systems(Update, hide_collected_objects)
+            .add_systems(Update, hide_filled_objects)
+            .add_systems(Update, ghost_inactive_traps.after(update_material_color))
+            .add_systems(
+                Update,
+                highlight_controlled_player.after(update_material_color),
+            )
             .add_systems(Update, update_transform_from_grid);
     }
 }
 
 fn spawn_player_object(
     q_added_player: Query<(Entity, &GridPos, &GameColor), Added<Player>>,
+    level_size: Res<LevelSize>,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
@@ -26,6 +48,7 @@ fn spawn_player_object(
             entity,
             color,
             pos,
+            &level_size,
             &mut commands,
             meshes.add(Mesh::from(shape::UVSphere {
                 radius: 0.5,
@@ -38,6 +61,7 @@ fn spawn_player_object(
 
 fn spawn_trap_object(
     q_added_trap: Query<(Entity, &GridPos, &GameColor), Added<Trap>>,
+    level_size: Res<LevelSize>,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
@@ -47,6 +71,7 @@ fn spawn_trap_object(
             entity,
             color,
             pos,
+            &level_size,
             &mut commands,
             meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
             &mut materials,
@@ -56,6 +81,7 @@ fn spawn_trap_object(
 
 fn spawn_goal_object(
     q_added_goal: Query<(Entity, &GridPos, &GameColor), Added<Goal>>,
+    level_size: Res<LevelSize>,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
@@ -65,6 +91,7 @@ fn spawn_goal_object(
             entity,
             color,
             pos,
+            &level_size,
             &mut commands,
             meshes.add(Mesh::from(shape::Torus {
                 radius: 0.5,
@@ -76,10 +103,245 @@ fn spawn_goal_object(
     }
 }
 
+fn spawn_wall_object(
+    q_added_wall: Query<(Entity, &GridPos, &GameColor), Added<Wall>>,
+    level_size: Res<LevelSize>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity, &pos, &color) in q_added_wall.iter() {
+        spawn_world_object(
+            entity,
+            color,
+            pos,
+            &level_size,
+            &mut commands,
+            meshes.add(Mesh::from(shape::Box::new(1.0, 1.5, 1.0))),
+            &mut materials,
+        );
+    }
+}
+
+fn spawn_paint_tile_object(
+    q_added_tile: Query<(Entity, &GridPos, &GameColor), Added<PaintTile>>,
+    level_size: Res<LevelSize>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity, &pos, &color) in q_added_tile.iter() {
+        spawn_world_object(
+            entity,
+            color,
+            pos,
+            &level_size,
+            &mut commands,
+            // A thin plate lying on the floor tile
+            meshes.add(Mesh::from(shape::Box {
+                min_x: -0.4,
+                max_x: 0.4,
+                min_y: -0.5,
+                max_y: -0.4,
+                min_z: -0.4,
+                max_z: 0.4,
+            })),
+            &mut materials,
+        );
+    }
+}
+
+fn spawn_teleporter_object(
+    q_added_teleporter: Query<(Entity, &GridPos, &GameColor), Added<Teleporter>>,
+    level_size: Res<LevelSize>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity, &pos, &color) in q_added_teleporter.iter() {
+        spawn_world_object(
+            entity,
+            color,
+            pos,
+            &level_size,
+            &mut commands,
+            // A pad standing a bit higher than paint tiles, so the two can be told apart
+            meshes.add(Mesh::from(shape::Box {
+                min_x: -0.3,
+                max_x: 0.3,
+                min_y: -0.5,
+                max_y: -0.2,
+                min_z: -0.3,
+                max_z: 0.3,
+            })),
+            &mut materials,
+        );
+    }
+}
+
+fn spawn_ice_object(
+    q_added_ice: Query<(Entity, &GridPos, &GameColor), Added<Ice>>,
+    level_size: Res<LevelSize>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity, &pos, &color) in q_added_ice.iter() {
+        spawn_world_object(
+            entity,
+            color,
+            pos,
+            &level_size,
+            &mut commands,
+            // A sheet covering the whole floor tile, under anything standing on it
+            meshes.add(Mesh::from(shape::Box {
+                min_x: -0.5,
+                max_x: 0.5,
+                min_y: -0.5,
+                max_y: -0.45,
+                min_z: -0.5,
+                max_z: 0.5,
+            })),
+            &mut materials,
+        );
+    }
+}
+
+fn spawn_block_object(
+    q_added_block: Query<(Entity, &GridPos, &GameColor), Added<Block>>,
+    level_size: Res<LevelSize>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity, &pos, &color) in q_added_block.iter() {
+        spawn_world_object(
+            entity,
+            color,
+            pos,
+            &level_size,
+            &mut commands,
+            meshes.add(Mesh::from(shape::Cube { size: 0.8 })),
+            &mut materials,
+        );
+    }
+}
+
+fn spawn_key_object(
+    q_added_key: Query<(Entity, &GridPos, &GameColor), Added<Key>>,
+    level_size: Res<LevelSize>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity, &pos, &color) in q_added_key.iter() {
+        spawn_world_object(
+            entity,
+            color,
+            pos,
+            &level_size,
+            &mut commands,
+            meshes.add(Mesh::from(shape::Capsule {
+                radius: 0.15,
+                depth: 0.4,
+                ..default()
+            })),
+            &mut materials,
+        );
+    }
+}
+
+fn spawn_door_object(
+    q_added_door: Query<(Entity, &GridPos, &GameColor), Added<Door>>,
+    level_size: Res<LevelSize>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity, &pos, &color) in q_added_door.iter() {
+        spawn_world_object(
+            entity,
+            color,
+            pos,
+            &level_size,
+            &mut commands,
+            // Narrower than a wall, so the two can be told apart
+            meshes.add(Mesh::from(shape::Box::new(1.0, 1.5, 0.3))),
+            &mut materials,
+        );
+    }
+}
+
+fn spawn_switch_object(
+    q_added_switch: Query<(Entity, &GridPos, &GameColor), Added<Switch>>,
+    level_size: Res<LevelSize>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity, &pos, &color) in q_added_switch.iter() {
+        spawn_world_object(
+            entity,
+            color,
+            pos,
+            &level_size,
+            &mut commands,
+            // A small button on the floor tile
+            meshes.add(Mesh::from(shape::Box {
+                min_x: -0.2,
+                max_x: 0.2,
+                min_y: -0.5,
+                max_y: -0.35,
+                min_z: -0.2,
+                max_z: 0.2,
+            })),
+            &mut materials,
+        );
+    }
+}
+
+/// Briefly scales an object up, then back down
+#[derive(Component)]
+struct Pulse(Timer);
+
+fn pulse_teleporters_on_teleport(
+    mut ev_teleported: EventReader<TeleportedEvent>,
+    q_teleporters: Query<(Entity, &GridPos), With<Teleporter>>,
+    mut commands: Commands,
+) {
+    for ev in ev_teleported.iter() {
+        for (entity, &pos) in q_teleporters.iter() {
+            if pos == ev.from || pos == ev.to {
+                commands
+                    .entity(entity)
+                    .insert(Pulse(Timer::from_seconds(0.3, TimerMode::Once)));
+            }
+        }
+    }
+}
+
+fn animate_pulse(
+    mut q_pulses: Query<(Entity, &mut Pulse, &mut Transform)>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (entity, mut pulse, mut transform) in q_pulses.iter_mut() {
+        pulse.0.tick(time.delta());
+        let bump = (pulse.0.percent() * std::f32::consts::PI).sin() * 0.5;
+        transform.scale = Vec3::splat(1.0 + bump);
+
+        if pulse.0.finished() {
+            transform.scale = Vec3::ONE;
+            commands.entity(entity).remove::<Pulse>();
+        }
+    }
+}
+
 fn spawn_world_object(
     entity: Entity,
     color: GameColor,
     pos: GridPos,
+    level_size: &LevelSize,
     commands: &mut Commands,
     mesh: Handle<Mesh>,
     materials: &mut ResMut<Assets<StandardMaterial>>,
@@ -92,7 +354,7 @@ fn spawn_world_object(
     commands.entity(entity).insert(PbrBundle {
         mesh,
         material: material_handle,
-        transform: Transform::from_translation(grid_to_translation(pos)),
+        transform: Transform::from_translation(grid_to_translation(pos, level_size)),
 
         ..default()
     });
@@ -112,6 +374,68 @@ fn update_visibility(
     }
 }
 
+fn hide_collected_objects(mut q_goals: Query<(&Collected, &mut Visibility), Changed<Collected>>) {
+    for (collected, mut visibility) in q_goals.iter_mut() {
+        *visibility = if collected.0 {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+    }
+}
+
+fn hide_filled_objects(mut q_filled: Query<(&Filled, &mut Visibility), Changed<Filled>>) {
+    for (filled, mut visibility) in q_filled.iter_mut() {
+        *visibility = if filled.0 {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+    }
+}
+
+/// Makes switched off traps see-through
+fn ghost_inactive_traps(
+    q_traps: Query<
+        (&Active, &Handle<StandardMaterial>),
+        Or<(Changed<Active>, Added<Handle<StandardMaterial>>)>,
+    >,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (active, material_handle) in q_traps.iter() {
+        let material = some_or_continue!(materials.get_mut(material_handle));
+        let alpha = if active.0 { 1.0 } else { 0.2 };
+        material.base_color.set_a(alpha);
+        material.alpha_mode = if active.0 {
+            AlphaMode::Opaque
+        } else {
+            AlphaMode::Blend
+        };
+    }
+}
+
+/// Makes the player in control glow in their color
+fn highlight_controlled_player(
+    q_players: Query<
+        (&Controlled, &Handle<StandardMaterial>),
+        Or<(
+            Changed<Controlled>,
+            Changed<GameColor>,
+            Added<Handle<StandardMaterial>>,
+        )>,
+    >,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (controlled, material_handle) in q_players.iter() {
+        let material = some_or_continue!(materials.get_mut(material_handle));
+        material.emissive = if controlled.0 {
+            material.base_color * 0.6
+        } else {
+            Color::BLACK
+        };
+    }
+}
+
 fn layers_from_game_color(game_color: GameColor) -> RenderLayers {
     let layers: Vec<u8> = match game_color {
         GameColor::Red => vec![1],
@@ -132,8 +456,10 @@ fn update_material_color(
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
     for (&color, material_handle) in &mut q_game_materials.iter_mut() {
-        let mut color_mat = materials.get_mut(&material_handle).unwrap();
-        color_mat.base_color = get_bevy_color(color);
+        let color_mat = materials.get_mut(&material_handle).unwrap();
+        // Keep the transparency of switched off traps
+        let alpha = color_mat.base_color.a();
+        color_mat.base_color = get_bevy_color(color).with_a(alpha);
     }
 }
 
@@ -151,15 +477,24 @@ fn get_bevy_color(game_color: GameColor) -> Color {
 
 fn update_transform_from_grid(
     mut q_transform_pos: Query<(&mut Transform, &GridPos), Or<(Added<GridPos>, Changed<GridPos>)>>,
+    level_size: Res<LevelSize>,
 ) {
     for transform_pos in q_transform_pos.iter_mut() {
         let mut transform = transform_pos.0;
         let pos = transform_pos.1;
 
-        transform.translation = grid_to_translation(*pos);
+        transform.translation = grid_to_translation(*pos, &level_size);
     }
 }
 
-fn grid_to_translation(pos: GridPos) -> Vec3 {
-    Vec3::new(pos.x as f32, 1.0, (GRID_SIZE_Y - pos.y - 1) as f32)
+fn grid_to_translation(pos: GridPos, level_size: &LevelSize) -> Vec3 {
+    Vec3::new(pos.x as f32, 1.0, (level_size.height - pos.y - 1) as f32)
+}
+
+/// The cell a point in the world is over, whatever its height
+pub fn translation_to_grid(translation: Vec3, level_size: &LevelSize) -> GridPos {
+    GridPos {
+        x: translation.x.round() as i32,
+        y: level_size.height - 1 - translation.z.round() as i32,
+    }
 }
@@ -0,0 +1,123 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+use ctrl_macros::{ok_or_return, some_or_return};
+
+use crate::{
+    controls::{update_actions, InputAction, Rebinding},
+    game_mechanics::{Direction, Game, GridPos, LevelSize},
+    object_rendering::translation_to_grid,
+};
+
+/// Height of the top of the floor tiles, where clicks land
+const FLOOR_TOP: f32 = 0.475;
+
+pub struct PointerPlugin;
+
+impl Plugin for PointerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, click_to_move.after(update_actions));
+    }
+}
+
+/// Steps the player toward the cell clicked or tapped in any of the three views, unless rebinding
+fn click_to_move(
+    mouse: Res<Input<MouseButton>>,
+    touches: Res<Touches>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    q_cameras: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    game: Res<Game>,
+    level_size: Res<LevelSize>,
+    rebinding: Res<Rebinding>,
+    mut actions: ResMut<Input<InputAction>>,
+) {
+    if rebinding.0.is_some() {
+        return;
+    }
+    let window = ok_or_return!(q_window.get_single());
+    let click = if mouse.just_pressed(MouseButton::Left) {
+        window.cursor_position()
+    } else {
+        touches
+            .iter_just_pressed()
+            .next()
+            .map(|touch| touch.position())
+    };
+    let click = some_or_return!(click);
+
+    // Each view is drawn where its camera's viewport is on the window, in physical pixels
+    let point = click * window.scale_factor() as f32;
+    let target = some_or_return!(q_cameras
+        .iter()
+        .find_map(|(camera, transform)| clicked_cell(camera, transform, point, &level_size)));
+
+    let game = some_or_return!(&game.0);
+    let player = some_or_return!(game.players.get(game.active_player.unwrap_or(0)));
+    let direction = some_or_return!(step_toward(player.pos, target));
+
+    actions.press(match direction {
+        Direction::Up => InputAction::MoveUp,
+        Direction::Down => InputAction::MoveDown,
+        Direction::Left => InputAction::MoveLeft,
+        Direction::Right => InputAction::MoveRight,
+    });
+}
+
+/// The cell seen at a point of the window through a camera, if the point is in its viewport
+fn clicked_cell(
+    camera: &Camera,
+    transform: &GlobalTransform,
+    point: Vec2,
+    level_size: &LevelSize,
+) -> Option<GridPos> {
+    let viewport = camera.viewport.as_ref()?;
+    let offset = point - viewport.physical_position.as_vec2();
+    if offset.cmplt(Vec2::ZERO).any() || offset.cmpge(viewport.physical_size.as_vec2()).any() {
+        return None;
+    }
+
+    // The views render to images, which don't scale, so the offset works as a logical position
+    let ray = camera.viewport_to_world(transform, offset)?;
+    let distance = ray.intersect_plane(Vec3::Y * FLOOR_TOP, Vec3::Y)?;
+    let pos = translation_to_grid(ray.get_point(distance), level_size);
+
+    level_size.contains(pos).then_some(pos)
+}
+
+/// First step from one cell toward another, along whichever axis they're further apart on
+fn step_toward(from: GridPos, to: GridPos) -> Option<Direction> {
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    if dx == 0 && dy == 0 {
+        None
+    } else if dx.abs() > dy.abs() {
+        Some(if dx > 0 {
+            Direction::Right
+        } else {
+            Direction::Left
+        })
+    } else {
+        Some(if dy > 0 {
+            Direction::Up
+        } else {
+            Direction::Down
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_along_the_longer_axis() {
+        let from = GridPos { x: 2, y: 2 };
+
+        assert_eq!(
+            step_toward(from, GridPos { x: 5, y: 3 }),
+            Some(Direction::Right)
+        );
+        assert_eq!(
+            step_toward(from, GridPos { x: 1, y: -2 }),
+            Some(Direction::Down)
+        );
+        assert_eq!(step_toward(from, from), None);
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use ctrl_macros::some_or_return;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game_mechanics::{count_moves, MoveCounter, ReachedGoalEvent},
+    level::{load_level_on_level_change, LevelInfo},
+};
+
+/// Where progress is kept between runs
+#[cfg(not(target_arch = "wasm32"))]
+const PROGRESS_PATH: &str = "progress.json";
+
+/// Where the web build keeps progress between runs, in the browser's local storage
+#[cfg(target_arch = "wasm32")]
+const PROGRESS_KEY: &str = "rgb-progress";
+
+pub struct ProgressPlugin;
+
+/// Best result of every level beaten so far, by level index
+#[derive(Resource, Default, Serialize, Deserialize)]
+pub struct Progress {
+    pub levels: BTreeMap<usize, LevelRecord>,
+    /// The level beaten most recently, and how
+    #[serde(skip)]
+    pub last: Option<(usize, LevelRecord)>,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct LevelRecord {
+    pub moves: u32,
+    pub par: Option<u32>,
+    /// Out of 3, for levels with a par
+    pub stars: Option<u8>,
+}
+
+impl LevelRecord {
+    fn new(moves: u32, par: Option<u32>) -> LevelRecord {
+        LevelRecord {
+            moves,
+            par,
+            stars: par.map(|par| stars(moves, par)),
+        }
+    }
+}
+
+/// Three stars for matching par, two for needing up to half as many moves again, one otherwise
+pub fn stars(moves: u32, par: u32) -> u8 {
+    if moves <= par {
+        3
+    } else if moves <= par + par / 2 {
+        2
+    } else {
+        1
+    }
+}
+
+impl Plugin for ProgressPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(load_progress()).add_systems(
+            Update,
+            record_progress_on_goal
+                .after(count_moves)
+                .before(load_level_on_level_change),
+        );
+    }
+}
+
+fn record_progress_on_goal(
+    mut ev_reached_goal: EventReader<ReachedGoalEvent>,
+    level_info: Res<LevelInfo>,
+    move_counter: Res<MoveCounter>,
+    mut progress: ResMut<Progress>,
+) {
+    if ev_reached_goal.iter().count() == 0 {
+        return;
+    }
+    let index = some_or_return!(level_info
+        .index
+        .and_then(|index| usize::try_from(index).ok()));
+
+    let record = LevelRecord::new(move_counter.0, level_info.par);
+    progress.last = Some((index, record));
+
+    let best = progress.levels.get(&index);
+    if best.is_none_or(|best| record.moves < best.moves) {
+        progress.levels.insert(index, record);
+        save_progress(&progress);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn load_progress() -> Progress {
+    let bytes = match std::fs::read(PROGRESS_PATH) {
+        Ok(bytes) => bytes,
+        Err(_) => return Progress::default(),
+    };
+
+    serde_json::from_slice(&bytes).unwrap_or_else(|err| {
+        warn!("Ignoring unreadable {PROGRESS_PATH}: {err}");
+        Progress::default()
+    })
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+fn load_progress() -> Progress {
+    let json = match local_storage().and_then(|storage| storage.get_item(PROGRESS_KEY).ok()?) {
+        Some(json) => json,
+        None => return Progress::default(),
+    };
+
+    serde_json::from_str(&json).unwrap_or_else(|err| {
+        warn!("Ignoring unreadable {PROGRESS_KEY}: {err}");
+        Progress::default()
+    })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_progress(progress: &Progress) {
+    let result = serde_json::to_vec_pretty(progress)
+        .map_err(anyhow::Error::from)
+        .and_then(|bytes| Ok(std::fs::write(PROGRESS_PATH, bytes)?));
+
+    if let Err(err) = result {
+        warn!("Couldn't save progress to {PROGRESS_PATH}: {err}");
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn save_progress(progress: &Progress) {
+    let result = serde_json::to_string(progress)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| {
+            let storage = local_storage().ok_or_else(|| anyhow::anyhow!("no local storage"))?;
+            storage
+                .set_item(PROGRESS_KEY, &json)
+                .map_err(|err| anyhow::anyhow!("{err:?}"))
+        });
+
+    if let Err(err) = result {
+        warn!("Couldn't save progress to {PROGRESS_KEY}: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stars_drop_as_moves_go_over_par() {
+        assert_eq!(stars(8, 10), 3);
+        assert_eq!(stars(10, 10), 3);
+        assert_eq!(stars(15, 10), 2);
+        assert_eq!(stars(16, 10), 1);
+    }
+}
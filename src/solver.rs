@@ -0,0 +1,203 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    time::Duration,
+};
+
+use crate::{
+    game_mechanics::Direction,
+    game_state::{Action, GameState, Outcome, Rules, Status},
+    level::Level,
+};
+
+/// Moves per second a player can be expected to make, used to turn time limits into move budgets
+pub const MOVES_PER_SECOND: f32 = 4.0;
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Up,
+    Direction::Down,
+    Direction::Left,
+    Direction::Right,
+];
+
+/// Finds the shortest sequence of moves that wins the level, playing by the rules of [`GameState`].
+/// Time passes at [`MOVES_PER_SECOND`], without ever waiting in place.
+/// Only actions that [spend a move](Outcome::is_move) are tried, switching players included.
+/// Returns `None` if the level can't be won within `max_moves`.
+pub fn solve(level: &Level, rules: Rules, max_moves: Option<usize>) -> Option<Vec<Action>> {
+    let start = GameState::new(level, rules);
+
+    // Breadth-first search over every state the level can be in
+    let mut seen = HashSet::from([start.clone()]);
+    let mut queue = VecDeque::from([(start, vec![])]);
+
+    while let Some((state, path)) = queue.pop_front() {
+        if max_moves.is_some_and(|max_moves| path.len() >= max_moves) {
+            continue;
+        }
+
+        let switch = state.active_player.map(|_| Action::SwitchPlayer);
+        let moves = DIRECTIONS.into_iter().map(Action::Move).chain(switch);
+
+        for action in moves {
+            let mut next = state.clone();
+            if !next.step(action).into_iter().any(Outcome::is_move) {
+                continue;
+            }
+            next.step(Action::Tick(Duration::from_secs_f32(
+                1.0 / MOVES_PER_SECOND,
+            )));
+
+            match next.status {
+                Status::Won => return Some([path.as_slice(), &[action]].concat()),
+                Status::Lost => continue,
+                Status::Playing => {}
+            }
+
+            if seen.insert(next.clone()) {
+                queue.push_back((next, [path.as_slice(), &[action]].concat()));
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::{
+        game_mechanics::GameColor,
+        game_state::{TrapRule, WinCondition},
+        level::parse_levels,
+        test_support::test_level,
+    };
+
+    fn level(player: (i32, i32), goals: &[(i32, i32)], traps: &[(i32, i32)]) -> Level {
+        let object = |(x, y): (i32, i32), color: &str| json!({ "x": x, "y": y, "color": color });
+
+        test_level(
+            3,
+            3,
+            json!({
+                "player": object(player, "White"),
+                "goals": goals.iter().map(|&pos| object(pos, "White")).collect::<Vec<_>>(),
+                "traps": traps.iter().map(|&pos| object(pos, "Red")).collect::<Vec<_>>(),
+            }),
+        )
+    }
+
+    #[test]
+    fn finds_shortest_path_around_traps() {
+        let level = level((0, 0), &[(2, 0)], &[(1, 0)]);
+        let path = solve(&level, Rules::default(), None).unwrap();
+        assert_eq!(path.len(), 4);
+        assert_eq!(path[0], Action::Move(Direction::Up));
+    }
+
+    #[test]
+    fn walled_off_goal_is_unreachable() {
+        let level = level((0, 0), &[(2, 2)], &[(1, 0), (1, 1), (1, 2)]);
+        assert_eq!(solve(&level, Rules::default(), None), None);
+    }
+
+    #[test]
+    fn respects_move_budget() {
+        let level = level((0, 0), &[(2, 2)], &[]);
+        assert_eq!(solve(&level, Rules::default(), Some(3)), None);
+        assert_eq!(
+            solve(&level, Rules::default(), Some(4)).map(|path| path.len()),
+            Some(4)
+        );
+    }
+
+    #[test]
+    fn traps_can_be_passed_by_players_of_other_channels() {
+        let mut level = level((0, 0), &[(2, 0)], &[(1, 0), (1, 1), (1, 2)]);
+        level.players[0].color = GameColor::Blue;
+        let rules = Rules {
+            traps: TrapRule::SharedChannel,
+            ..Rules::default()
+        };
+
+        assert_eq!(solve(&level, Rules::default(), None), None);
+        assert_eq!(solve(&level, rules, None).map(|path| path.len()), Some(2));
+    }
+
+    #[test]
+    fn all_goals_have_to_be_collected() {
+        let mut level = level((0, 0), &[(2, 0), (0, 2)], &[]);
+        assert_eq!(
+            solve(&level, Rules::default(), None).map(|p| p.len()),
+            Some(2)
+        );
+
+        level.win_condition = WinCondition::AllGoals;
+        assert_eq!(
+            solve(&level, Rules::default(), None).map(|p| p.len()),
+            Some(6)
+        );
+    }
+
+    #[test]
+    fn switching_players_moves_one_at_a_time() {
+        let mut level = level((0, 0), &[(2, 0), (2, 1)], &[]);
+        level.goals[1].color = GameColor::Blue;
+        level
+            .players
+            .push(serde_json::from_value(json!({ "x": 0, "y": 1, "color": "Blue" })).unwrap());
+        assert_eq!(
+            solve(&level, Rules::default(), None).map(|p| p.len()),
+            Some(2)
+        );
+
+        level.players[0].active = true;
+        let path = solve(&level, Rules::default(), None).unwrap();
+        assert_eq!(path.len(), 5);
+        assert_eq!(path[2], Action::SwitchPlayer);
+
+        // Every action spends a move, so par is what the move counter shows
+        let mut state = GameState::new(&level, Rules::default());
+        for action in path {
+            assert!(state.step(action).into_iter().any(Outcome::is_move));
+        }
+    }
+
+    #[test]
+    fn turn_based_levels_run_out_of_moves() {
+        let mut level = level((0, 0), &[(2, 2)], &[]);
+        level.time_limit = Some(0.1);
+        level.move_limit = Some(4);
+        assert_eq!(
+            solve(&level, Rules::default(), None).map(|p| p.len()),
+            Some(4)
+        );
+
+        level.move_limit = Some(3);
+        assert_eq!(solve(&level, Rules::default(), None), None);
+    }
+
+    #[test]
+    fn every_shipped_level_is_beatable() {
+        let bytes = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/assets/levels/bevy-jam-2-levels.ldtk"
+        ))
+        .unwrap();
+        let levels = parse_levels(&bytes, true).unwrap();
+
+        for (index, level) in levels.levels.iter().enumerate() {
+            let max_moves = level
+                .time_limit
+                .map(|seconds| (seconds * MOVES_PER_SECOND) as usize);
+            let path = solve(level, levels.rules, max_moves);
+            assert!(path.is_some(), "level {index} can't be beaten");
+
+            // Par should be the best possible, neither unreachable nor easy to beat
+            if let (Some(par), Some(path)) = (level.par, path) {
+                assert_eq!(path.len() as u32, par, "level {index} has the wrong par");
+            }
+        }
+    }
+}
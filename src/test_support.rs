@@ -0,0 +1,23 @@
+use serde_json::json;
+
+use crate::level::Level;
+
+/// A `width` by `height` level for tests, with a white player in the corner and no timer, goals
+/// or traps. `fields` adds to or replaces any of those.
+pub fn test_level(width: i32, height: i32, fields: serde_json::Value) -> Level {
+    let mut level = json!({
+        "width": width,
+        "height": height,
+        "time_limit": null,
+        "player": { "x": 0, "y": 0, "color": "White" },
+        "goals": [],
+        "traps": [],
+    });
+    let level_fields = level.as_object_mut().unwrap();
+    if fields.get("players").is_some() {
+        level_fields.remove("player");
+    }
+    level_fields.extend(fields.as_object().cloned().unwrap_or_default());
+
+    serde_json::from_value(level).unwrap()
+}
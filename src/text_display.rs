@@ -1,6 +1,12 @@
 use bevy::prelude::*;
 
-use crate::{game_mechanics::GameTimer, level::LevelInfo};
+use crate::{
+    controls::{Bindings, Rebinding, REBIND_KEY},
+    game_mechanics::{Game, Inventory, MoveCounter, Paused, Player},
+    game_state::WinCondition,
+    level::LevelInfo,
+    progress::Progress,
+};
 
 pub struct TextDisplayPlugin;
 
@@ -8,13 +14,33 @@ impl Plugin for TextDisplayPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, setup_text)
             .add_systems(Update, win_text)
-            .add_systems(Update, text_update_system);
+            .add_systems(Update, text_update_system)
+            .add_systems(Update, goals_text_system)
+            .add_systems(Update, keys_text_system)
+            .add_systems(Update, moves_text_system)
+            .add_systems(Update, rating_text_system)
+            .add_systems(Update, controls_text_system);
     }
 }
 
 #[derive(Component)]
 struct TimerText;
 
+#[derive(Component)]
+struct GoalsText;
+
+#[derive(Component)]
+struct KeysText;
+
+#[derive(Component)]
+struct MovesText;
+
+#[derive(Component)]
+struct RatingText;
+
+#[derive(Component)]
+struct ControlsText;
+
 fn setup_text(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands
         .spawn(
@@ -38,26 +64,111 @@ fn setup_text(mut commands: Commands, asset_server: Res<AssetServer>) {
         )
         .insert(TimerText);
 
-    commands.spawn(
-        TextBundle::from_section(
-            "Controls: WASD or Arrow Keys for movement
-			Reach the goal (ring) without hitting any walls",
-            TextStyle {
-                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-                font_size: 20.0,
-                color: Color::WHITE,
-            },
+    commands
+        .spawn(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 35.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+
+                left: Val::Percent(5.0),
+                bottom: Val::Percent(0.0),
+                ..default()
+            }),
         )
-        .with_text_alignment(TextAlignment::Center)
-        .with_style(Style {
-            align_self: AlignSelf::FlexEnd,
-            position_type: PositionType::Absolute,
+        .insert(GoalsText);
 
-            top: Val::Percent(0.0),
-            right: Val::Percent(0.0),
-            ..default()
-        }),
-    );
+    commands
+        .spawn(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 35.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+
+                left: Val::Percent(5.0),
+                bottom: Val::Percent(6.0),
+                ..default()
+            }),
+        )
+        .insert(KeysText);
+
+    commands
+        .spawn(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 35.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+
+                right: Val::Percent(5.0),
+                bottom: Val::Percent(0.0),
+                ..default()
+            }),
+        )
+        .insert(MovesText);
+
+    commands
+        .spawn(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 25.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+
+                right: Val::Percent(5.0),
+                bottom: Val::Percent(6.0),
+                ..default()
+            }),
+        )
+        .insert(RatingText);
+
+    commands
+        .spawn(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_text_alignment(TextAlignment::Center)
+            .with_style(Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+
+                top: Val::Percent(0.0),
+                right: Val::Percent(0.0),
+                ..default()
+            }),
+        )
+        .insert(ControlsText);
 }
 
 fn win_text(mut commands: Commands, asset_server: Res<AssetServer>, level_info: Res<LevelInfo>) {
@@ -92,15 +203,26 @@ fn win_text(mut commands: Commands, asset_server: Res<AssetServer>, level_info:
 }
 
 fn text_update_system(
-    timer: Res<GameTimer>,
+    game: Res<Game>,
     mut query: Query<&mut Text, With<TimerText>>,
     level_info: Res<LevelInfo>,
+    paused: Res<Paused>,
 ) {
     let mut one_second = false;
     let mut two_seconds = false;
-    let mut timer_text = match &timer.0 {
-        Some(timer) => {
-            let remaining = (timer.duration().as_millis() as f32 / 1000.0) - timer.elapsed_secs();
+    let time_left = game.0.as_ref().and_then(|game| game.time_left);
+    let moves_left = game.0.as_ref().and_then(|game| game.moves_left);
+    let mut timer_text = match (time_left, moves_left) {
+        (_, Some(moves_left)) => {
+            if moves_left <= 1 {
+                one_second = true;
+            } else if moves_left <= 2 {
+                two_seconds = true;
+            }
+            format!("Moves left: {moves_left}")
+        }
+        (Some(time_left), None) => {
+            let remaining = time_left.as_secs_f32();
             if remaining <= 1.0 {
                 one_second = true;
             } else if remaining <= 2.0 {
@@ -108,9 +230,15 @@ fn text_update_system(
             }
             format!("Timer: {:.0}", remaining + 0.5)
         }
-        None => "".to_owned(),
+        (None, None) => "".to_owned(),
     };
 
+    if paused.0 {
+        timer_text = "Paused".to_owned();
+        one_second = false;
+        two_seconds = false;
+    }
+
     if level_info.index == Some(level_info.total_levels as i32) {
         timer_text = "".to_owned();
     }
@@ -127,3 +255,109 @@ fn text_update_system(
         }
     }
 }
+
+fn goals_text_system(game: Res<Game>, mut query: Query<&mut Text, With<GoalsText>>) {
+    let goals_text = match &game.0 {
+        Some(game) if game.win_condition != WinCondition::AnyGoal => {
+            format!("Goals: {}/{}", game.goals_collected(), game.goals.len())
+        }
+        _ => "".to_owned(),
+    };
+
+    for mut text in &mut query {
+        text.sections[0].value = goals_text.clone();
+    }
+}
+
+fn keys_text_system(
+    q_inventory: Query<&Inventory, With<Player>>,
+    mut query: Query<&mut Text, With<KeysText>>,
+) {
+    // Keys are shared, so any player's inventory will do
+    let keys_text = match q_inventory.iter().next() {
+        Some(inventory) if !inventory.0.is_empty() => {
+            let keys: Vec<String> = inventory.0.iter().map(|key| format!("{key:?}")).collect();
+            format!("Keys: {}", keys.join(", "))
+        }
+        _ => "".to_owned(),
+    };
+
+    for mut text in &mut query {
+        text.sections[0].value = keys_text.clone();
+    }
+}
+
+fn moves_text_system(
+    game: Res<Game>,
+    move_counter: Res<MoveCounter>,
+    level_info: Res<LevelInfo>,
+    progress: Res<Progress>,
+    mut query: Query<&mut Text, With<MovesText>>,
+) {
+    let mut moves_text = match &game.0 {
+        Some(_) => format!("Moves: {}", move_counter.0),
+        None => "".to_owned(),
+    };
+    if let (Some(_), Some(par)) = (&game.0, level_info.par) {
+        moves_text += &format!(" / Par: {par}");
+    }
+    let best = level_info
+        .index
+        .and_then(|index| usize::try_from(index).ok())
+        .and_then(|index| progress.levels.get(&index));
+    if let (Some(_), Some(best)) = (&game.0, best) {
+        moves_text += &format!(" / Best: {}", best.moves);
+    }
+
+    for mut text in &mut query {
+        text.sections[0].value = moves_text.clone();
+    }
+}
+
+/// Rates the level beaten most recently
+fn rating_text_system(progress: Res<Progress>, mut query: Query<&mut Text, With<RatingText>>) {
+    if !progress.is_changed() {
+        return;
+    }
+
+    let rating_text = match progress.last {
+        Some((index, record)) => {
+            let mut rating = format!("Level {} beaten in {} moves", index + 1, record.moves);
+            if let Some(stars) = record.stars {
+                rating += &format!(", {stars}/3 stars");
+            }
+            rating
+        }
+        None => "".to_owned(),
+    };
+
+    for mut text in &mut query {
+        text.sections[0].value = rating_text.clone();
+    }
+}
+
+/// Lists the current key bindings, so rebinding a key shows up straight away
+fn controls_text_system(
+    bindings: Res<Bindings>,
+    rebinding: Res<Rebinding>,
+    mut query: Query<&mut Text, With<ControlsText>>,
+) {
+    if !bindings.is_changed() && !rebinding.is_changed() {
+        return;
+    }
+
+    let controls_text = match rebinding.0 {
+        Some(action) => format!(
+            "Press a key or button for {} ({REBIND_KEY:?} to stop)",
+            action.label()
+        ),
+        None => format!(
+            "Controls: {}, or click a cell to step toward it, {REBIND_KEY:?} to rebind\nReach the goal (ring) without hitting any traps (cubes)",
+            bindings.describe()
+        ),
+    };
+
+    for mut text in &mut query {
+        text.sections[0].value = controls_text.clone();
+    }
+}